@@ -61,3 +61,67 @@ pub fn wait() {
         mips_wait();
     }
 }
+
+/// Get the current interrupt priority level (IPL field of the CP0 Status
+/// register)
+#[inline]
+pub fn ipl() -> u8 {
+    extern "C" {
+        fn mips_get_ipl() -> u32;
+    }
+    unsafe { mips_get_ipl() as u8 }
+}
+
+/// Set the interrupt priority level (IPL field of the CP0 Status register)
+///
+/// Only interrupts having a priority higher than the IPL are serviced.
+///
+/// # Safety
+///
+/// Lowering the IPL may break critical sections relying on the IPL
+#[inline]
+pub unsafe fn set_ipl(ipl: u8) {
+    extern "C" {
+        fn mips_set_ipl(ipl: u32);
+    }
+    mips_set_ipl(ipl as u32);
+}
+
+/// Raise the interrupt priority level to `ceiling` and return the previous
+/// level
+///
+/// The interrupt priority level is left unchanged if it is already equal to
+/// or higher than `ceiling`.
+#[inline]
+pub fn raise_ipl(ceiling: u8) -> u8 {
+    extern "C" {
+        fn mips_raise_ipl(ceiling: u32) -> u32;
+    }
+    unsafe { mips_raise_ipl(ceiling as u32) as u8 }
+}
+
+/// Read the shadow register set control register (CP0 register 12, select 2)
+#[inline]
+pub fn read_srsctl() -> u32 {
+    extern "C" {
+        fn mips_read_srsctl() -> u32;
+    }
+    unsafe { mips_read_srsctl() }
+}
+
+/// Initialize the stack pointer of a shadow register set
+///
+/// Sets the stack pointer (`sp`) of the shadow register set `set` to
+/// `stack_pointer` and copies the global pointer (`gp`) of the current
+/// register set.
+///
+/// # Safety
+///
+/// `stack_pointer` must point to the top of a stack that is reserved for
+/// interrupt handlers using the shadow register set
+pub unsafe fn init_shadow_set(set: u8, stack_pointer: *mut u32) {
+    extern "C" {
+        fn mips_init_shadow_set(set: u32, stack_pointer: *mut u32);
+    }
+    mips_init_shadow_set(set as u32, stack_pointer);
+}
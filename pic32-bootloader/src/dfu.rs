@@ -0,0 +1,627 @@
+//! USB Device Firmware Upgrade (DFU) 1.1 classes
+//!
+//! `DfuClass` implements the DFU mode of the bootloader. Each downloaded block
+//! is programmed when the host requests the status (`DFU_GETSTATUS`) so that
+//! the host waits for the flash memory operations. The erase pages of the
+//! application region are erased as the download proceeds. The download is
+//! verified and the `ImageInfo` record is written in the manifestation phase.
+//! The device is manifestation tolerant and requests a reboot on the next bus
+//! reset after a successful manifestation.
+//!
+//! `DfuRuntimeClass` can be added to an application so that DFU host tools
+//! (e.g. `dfu-util`) can switch the device to the bootloader.
+
+use core::marker::PhantomData;
+
+use embedded_storage::nor_flash::NorFlash;
+use usb_device::class_prelude::*;
+use usb_device::control::{Recipient, Request, RequestType};
+use usb_device::Result;
+
+use crate::image::{image_crc, Crc32, ImageInfo};
+use crate::Config;
+
+const CLASS_APPLICATION_SPECIFIC: u8 = 0xfe;
+const SUBCLASS_DFU: u8 = 0x01;
+const PROTOCOL_RUNTIME: u8 = 0x01;
+const PROTOCOL_DFU_MODE: u8 = 0x02;
+
+const DESCRIPTOR_DFU_FUNCTIONAL: u8 = 0x21;
+
+// bmAttributes of the DFU functional descriptor
+const ATTR_CAN_DNLOAD: u8 = 0x01;
+const ATTR_MANIFESTATION_TOLERANT: u8 = 0x04;
+const ATTR_WILL_DETACH: u8 = 0x08;
+
+// class specific requests
+const DFU_DETACH: u8 = 0;
+const DFU_DNLOAD: u8 = 1;
+const DFU_GETSTATUS: u8 = 3;
+const DFU_CLRSTATUS: u8 = 4;
+const DFU_GETSTATE: u8 = 5;
+const DFU_ABORT: u8 = 6;
+
+/// Maximum number of bytes per download block (wTransferSize)
+///
+/// Must not exceed the control buffer size of the `usb-device` crate.
+pub const TRANSFER_SIZE: usize = 128;
+
+/// Time in ms the application waits for a bus reset after `DFU_DETACH`
+const DETACH_TIMEOUT_MS: u16 = 1000;
+
+/// Minimum time in ms between two `DFU_GETSTATUS` requests (bwPollTimeout)
+const POLL_TIMEOUT_MS: u8 = 5;
+
+const DFU_VERSION: u16 = 0x0110;
+
+/// String describing the DFU interface
+const INTERFACE_NAME: &str = "Application";
+
+/// DFU device states
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum State {
+    AppIdle = 0,
+    AppDetach = 1,
+    DfuIdle = 2,
+    DnloadSync = 3,
+    DnBusy = 4,
+    DnloadIdle = 5,
+    ManifestSync = 6,
+    Manifest = 7,
+    ManifestWaitReset = 8,
+    UploadIdle = 9,
+    Error = 10,
+}
+
+/// DFU status codes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Status {
+    Ok = 0x00,
+    ErrTarget = 0x01,
+    ErrFile = 0x02,
+    ErrWrite = 0x03,
+    ErrErase = 0x04,
+    ErrCheckErased = 0x05,
+    ErrProg = 0x06,
+    ErrVerify = 0x07,
+    ErrAddress = 0x08,
+    ErrNotDone = 0x09,
+    ErrFirmware = 0x0a,
+    ErrVendor = 0x0b,
+    ErrUsbr = 0x0c,
+    ErrPor = 0x0d,
+    ErrUnknown = 0x0e,
+    ErrStalledPkt = 0x0f,
+}
+
+/// Write a DFU functional descriptor
+fn write_functional_descriptor(writer: &mut DescriptorWriter, attributes: u8) -> Result<()> {
+    let timeout = DETACH_TIMEOUT_MS.to_le_bytes();
+    let transfer_size = (TRANSFER_SIZE as u16).to_le_bytes();
+    let version = DFU_VERSION.to_le_bytes();
+    writer.write(
+        DESCRIPTOR_DFU_FUNCTIONAL,
+        &[
+            attributes,
+            timeout[0],
+            timeout[1],
+            transfer_size[0],
+            transfer_size[1],
+            version[0],
+            version[1],
+        ],
+    )
+}
+
+/// Check if `req` is a DFU class request to interface `iface`
+fn is_dfu_request(req: &Request, iface: InterfaceNumber) -> bool {
+    req.request_type == RequestType::Class
+        && req.recipient == Recipient::Interface
+        && req.index == u8::from(iface) as u16
+}
+
+/// DFU mode class
+pub struct DfuClass<'a, B: UsbBus, F: NorFlash> {
+    iface: InterfaceNumber,
+    iface_string: StringIndex,
+    flash: F,
+    config: &'a Config,
+    state: State,
+    status: Status,
+    block: [u8; TRANSFER_SIZE],
+    block_len: usize,
+    /// Number of bytes downloaded and programmed
+    offset: u32,
+    /// End of the erased part of the application region (relative offset)
+    erased: u32,
+    info_erased: bool,
+    /// A block shorter than `TRANSFER_SIZE` has been received
+    short_block: bool,
+    crc: Crc32,
+    manifested: bool,
+    reboot: bool,
+    _bus: PhantomData<B>,
+}
+
+impl<'a, B: UsbBus, F: NorFlash> DfuClass<'a, B, F> {
+    /// Create a new DFU mode class
+    ///
+    /// `TRANSFER_SIZE` and the size of an `ImageInfo` record must be multiples
+    /// of `F::WRITE_SIZE`.
+    pub fn new(alloc: &'a UsbBusAllocator<B>, flash: F, config: &'a Config) -> Self {
+        assert!(TRANSFER_SIZE.is_multiple_of(F::WRITE_SIZE));
+        assert!(ImageInfo::SIZE.is_multiple_of(F::WRITE_SIZE));
+        DfuClass {
+            iface: alloc.interface(),
+            iface_string: alloc.string(),
+            flash,
+            config,
+            state: State::DfuIdle,
+            status: Status::Ok,
+            block: [0; TRANSFER_SIZE],
+            block_len: 0,
+            offset: 0,
+            erased: 0,
+            info_erased: false,
+            short_block: false,
+            crc: Crc32::new(),
+            manifested: false,
+            reboot: false,
+            _bus: PhantomData,
+        }
+    }
+
+    /// Current DFU state
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Check if a new image has been downloaded and a bus reset occurred
+    /// afterwards. The device should then be reset to start the new image.
+    pub fn reboot_requested(&self) -> bool {
+        self.reboot
+    }
+
+    /// Return the flash memory driver
+    pub fn free(self) -> F {
+        self.flash
+    }
+
+    fn begin_download(&mut self) {
+        self.offset = 0;
+        self.erased = 0;
+        self.info_erased = false;
+        self.short_block = false;
+        self.crc = Crc32::new();
+        self.manifested = false;
+    }
+
+    /// Program the received block
+    fn download_block(&mut self) -> core::result::Result<(), Status> {
+        if !self.info_erased {
+            // invalidate the current image before overwriting it
+            let from = self.config.info_offset::<F>();
+            self.flash
+                .erase(from, from + F::ERASE_SIZE as u32)
+                .map_err(|_| Status::ErrErase)?;
+            self.info_erased = true;
+        }
+        if self.short_block {
+            return Err(Status::ErrAddress);
+        }
+        let len = self.block_len;
+        let end = self.offset + len as u32;
+        if end > self.config.max_image_size::<F>() {
+            return Err(Status::ErrAddress);
+        }
+        while self.erased < end {
+            let from = self.config.app_offset + self.erased;
+            self.flash
+                .erase(from, from + F::ERASE_SIZE as u32)
+                .map_err(|_| Status::ErrErase)?;
+            self.erased += F::ERASE_SIZE as u32;
+        }
+        let padded = len.next_multiple_of(F::WRITE_SIZE);
+        self.block[len..padded].fill(0xff);
+        self.flash
+            .write(self.config.app_offset + self.offset, &self.block[..padded])
+            .map_err(|_| Status::ErrProg)?;
+        self.crc.update(&self.block[..len]);
+        self.offset = end;
+        self.short_block = len < TRANSFER_SIZE;
+        Ok(())
+    }
+
+    /// Verify the downloaded image and write the `ImageInfo` record
+    fn manifest(&mut self) -> core::result::Result<(), Status> {
+        let info = ImageInfo {
+            length: self.offset,
+            crc: self.crc.finish(),
+        };
+        match image_crc(&mut self.flash, self.config, info.length) {
+            Ok(crc) if crc == info.crc => {}
+            _ => return Err(Status::ErrVerify),
+        }
+        self.flash
+            .write(self.config.info_offset::<F>(), &info.to_bytes())
+            .map_err(|_| Status::ErrProg)?;
+        if ImageInfo::read(&mut self.flash, self.config) != Some(info) {
+            return Err(Status::ErrVerify);
+        }
+        Ok(())
+    }
+
+    /// Carry out the flash memory operations of the synchronization states
+    fn process(&mut self) {
+        let result = match self.state {
+            State::DnloadSync => self.download_block().map(|_| State::DnloadIdle),
+            State::ManifestSync => self.manifest().map(|_| {
+                self.manifested = true;
+                State::DfuIdle
+            }),
+            _ => return,
+        };
+        match result {
+            Ok(state) => self.state = state,
+            Err(status) => self.fail(status),
+        }
+    }
+
+    fn fail(&mut self, status: Status) {
+        self.state = State::Error;
+        self.status = status;
+    }
+
+    /// Handle `DFU_GETSTATUS` and return the status response
+    fn get_status(&mut self) -> [u8; 6] {
+        self.process();
+        [
+            self.status as u8,
+            POLL_TIMEOUT_MS,
+            0,
+            0,
+            self.state as u8,
+            0,
+        ]
+    }
+
+    /// Handle a request of the host-to-device direction
+    ///
+    /// Returns `false` if the request is to be rejected.
+    fn request_out(&mut self, request: u8, data: &[u8]) -> bool {
+        match (request, self.state) {
+            (DFU_DNLOAD, State::DfuIdle | State::DnloadIdle) if !data.is_empty() => {
+                if data.len() > TRANSFER_SIZE {
+                    self.fail(Status::ErrStalledPkt);
+                    return false;
+                }
+                if self.state == State::DfuIdle {
+                    self.begin_download();
+                }
+                self.block[..data.len()].copy_from_slice(data);
+                self.block_len = data.len();
+                self.state = State::DnloadSync;
+            }
+            (DFU_DNLOAD, State::DnloadIdle) => self.state = State::ManifestSync,
+            (DFU_CLRSTATUS, State::Error) => {
+                self.state = State::DfuIdle;
+                self.status = Status::Ok;
+            }
+            (DFU_ABORT, State::DfuIdle | State::DnloadIdle | State::ManifestSync) => {
+                self.state = State::DfuIdle;
+            }
+            _ => {
+                self.fail(Status::ErrStalledPkt);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<B: UsbBus, F: NorFlash> UsbClass<B> for DfuClass<'_, B, F> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.interface_alt(
+            self.iface,
+            0,
+            CLASS_APPLICATION_SPECIFIC,
+            SUBCLASS_DFU,
+            PROTOCOL_DFU_MODE,
+            Some(self.iface_string),
+        )?;
+        write_functional_descriptor(writer, ATTR_CAN_DNLOAD | ATTR_MANIFESTATION_TOLERANT)
+    }
+
+    fn get_string(&self, index: StringIndex, _lang_id: LangID) -> Option<&str> {
+        if index == self.iface_string {
+            Some(INTERFACE_NAME)
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        if self.manifested {
+            self.reboot = true;
+        }
+        self.state = State::DfuIdle;
+        self.status = Status::Ok;
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = *xfer.request();
+        if !is_dfu_request(&req, self.iface) {
+            return;
+        }
+        match req.request {
+            DFU_GETSTATUS => {
+                let status = self.get_status();
+                xfer.accept_with(&status).ok();
+            }
+            DFU_GETSTATE => {
+                xfer.accept_with(&[self.state as u8]).ok();
+            }
+            _ => {
+                self.fail(Status::ErrStalledPkt);
+                xfer.reject().ok();
+            }
+        }
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = *xfer.request();
+        if !is_dfu_request(&req, self.iface) {
+            return;
+        }
+        if self.request_out(req.request, xfer.data()) {
+            xfer.accept().ok();
+        } else {
+            xfer.reject().ok();
+        }
+    }
+}
+
+/// DFU runtime class
+///
+/// When the host sends `DFU_DETACH`, `detach_requested()` returns `true`. The
+/// application should then call `reboot_to_bootloader()`.
+pub struct DfuRuntimeClass {
+    iface: InterfaceNumber,
+    detach: bool,
+}
+
+impl DfuRuntimeClass {
+    /// Create a new DFU runtime class
+    pub fn new<B: UsbBus>(alloc: &UsbBusAllocator<B>) -> Self {
+        DfuRuntimeClass {
+            iface: alloc.interface(),
+            detach: false,
+        }
+    }
+
+    /// Check if the host requested to switch to DFU mode
+    pub fn detach_requested(&self) -> bool {
+        self.detach
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for DfuRuntimeClass {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.interface(
+            self.iface,
+            CLASS_APPLICATION_SPECIFIC,
+            SUBCLASS_DFU,
+            PROTOCOL_RUNTIME,
+        )?;
+        write_functional_descriptor(writer, ATTR_CAN_DNLOAD | ATTR_WILL_DETACH)
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = *xfer.request();
+        if !is_dfu_request(&req, self.iface) {
+            return;
+        }
+        let state = State::AppIdle as u8;
+        match req.request {
+            DFU_GETSTATUS => xfer.accept_with(&[Status::Ok as u8, 0, 0, 0, state, 0]),
+            DFU_GETSTATE => xfer.accept_with(&[state]),
+            _ => xfer.reject(),
+        }
+        .ok();
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = *xfer.request();
+        if !is_dfu_request(&req, self.iface) {
+            return;
+        }
+        if req.request == DFU_DETACH {
+            self.detach = true;
+            xfer.accept().ok();
+        } else {
+            xfer.reject().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image;
+    use crate::mock::MockFlash;
+    use usb_device::bus::PollResult;
+    use usb_device::endpoint::{EndpointAddress, EndpointType};
+    use usb_device::UsbDirection;
+
+    type Flash = MockFlash;
+
+    const CONFIG: Config = Config {
+        vid: 0x16c0,
+        pid: 0x27dd,
+        manufacturer: "Test",
+        product: "Test",
+        app_offset: 1024,
+        app_size: 3072,
+        app_entry: 0x9d00_0400,
+    };
+
+    /// USB bus driver that is only needed to create the class
+    struct MockBus;
+
+    impl UsbBus for MockBus {
+        fn alloc_ep(
+            &mut self,
+            _ep_dir: UsbDirection,
+            _ep_addr: Option<EndpointAddress>,
+            _ep_type: EndpointType,
+            _max_packet_size: u16,
+            _interval: u8,
+        ) -> Result<EndpointAddress> {
+            Err(UsbError::Unsupported)
+        }
+
+        fn enable(&mut self) {}
+
+        fn reset(&self) {}
+
+        fn set_device_address(&self, _addr: u8) {}
+
+        fn write(&self, _ep_addr: EndpointAddress, _buf: &[u8]) -> Result<usize> {
+            Err(UsbError::WouldBlock)
+        }
+
+        fn read(&self, _ep_addr: EndpointAddress, _buf: &mut [u8]) -> Result<usize> {
+            Err(UsbError::WouldBlock)
+        }
+
+        fn set_stalled(&self, _ep_addr: EndpointAddress, _stalled: bool) {}
+
+        fn is_stalled(&self, _ep_addr: EndpointAddress) -> bool {
+            false
+        }
+
+        fn suspend(&self) {}
+
+        fn resume(&self) {}
+
+        fn poll(&self) -> PollResult {
+            PollResult::None
+        }
+    }
+
+    type Dfu<'a> = DfuClass<'a, MockBus, Flash>;
+
+    /// Download a block and return the status response
+    fn download(dfu: &mut Dfu, data: &[u8]) -> [u8; 6] {
+        assert!(dfu.request_out(DFU_DNLOAD, data));
+        assert_eq!(dfu.state(), State::DnloadSync);
+        dfu.get_status()
+    }
+
+    fn image(len: usize) -> [u8; 1024] {
+        let mut image = [0; 1024];
+        for (i, byte) in image.iter_mut().enumerate().take(len) {
+            *byte = i as u8 ^ 0x5a;
+        }
+        image
+    }
+
+    #[test]
+    fn download_and_manifest() {
+        let alloc = UsbBusAllocator::new(MockBus);
+        let mut dfu = DfuClass::new(&alloc, Flash::new(), &CONFIG);
+        assert_eq!(dfu.state(), State::DfuIdle);
+
+        let image = image(300);
+        for block in image[..300].chunks(TRANSFER_SIZE) {
+            let status = download(&mut dfu, block);
+            assert_eq!(status[0], Status::Ok as u8);
+            assert_eq!(status[4], State::DnloadIdle as u8);
+        }
+        assert!(dfu.request_out(DFU_DNLOAD, &[]));
+        assert_eq!(dfu.state(), State::ManifestSync);
+        let status = dfu.get_status();
+        assert_eq!(status[0], Status::Ok as u8);
+        assert_eq!(status[4], State::DfuIdle as u8);
+
+        // the device reboots on the next bus reset
+        assert!(!dfu.reboot_requested());
+        UsbClass::<MockBus>::reset(&mut dfu);
+        assert!(dfu.reboot_requested());
+
+        let mut flash = dfu.free();
+        let mut crc = Crc32::new();
+        crc.update(&image[..300]);
+        let info = image::validate(&mut flash, &CONFIG).unwrap();
+        assert_eq!(info.length, 300);
+        assert_eq!(info.crc, crc.finish());
+        let start = CONFIG.app_offset as usize;
+        assert_eq!(&flash.data()[start..start + 300], &image[..300]);
+    }
+
+    #[test]
+    fn download_invalidates_image() {
+        let alloc = UsbBusAllocator::new(MockBus);
+        let mut dfu = DfuClass::new(&alloc, Flash::new(), &CONFIG);
+        download(&mut dfu, &image(16)[..16]);
+        assert!(dfu.request_out(DFU_DNLOAD, &[]));
+        dfu.get_status();
+        assert!(image::validate(&mut dfu.flash, &CONFIG).is_some());
+
+        // the first block of a new download erases the image information
+        download(&mut dfu, &image(16)[..16]);
+        assert!(dfu.request_out(DFU_ABORT, &[]));
+        assert_eq!(dfu.state(), State::DfuIdle);
+        assert_eq!(image::validate(&mut dfu.flash, &CONFIG), None);
+    }
+
+    #[test]
+    fn block_after_short_block() {
+        let alloc = UsbBusAllocator::new(MockBus);
+        let mut dfu = DfuClass::new(&alloc, Flash::new(), &CONFIG);
+        download(&mut dfu, &image(16)[..16]);
+        let status = download(&mut dfu, &image(16)[..16]);
+        assert_eq!(status[0], Status::ErrAddress as u8);
+        assert_eq!(status[4], State::Error as u8);
+
+        // only DFU_CLRSTATUS leaves the error state
+        assert!(!dfu.request_out(DFU_DNLOAD, &[0; 4]));
+        assert_eq!(dfu.state(), State::Error);
+        assert!(dfu.request_out(DFU_CLRSTATUS, &[]));
+        assert_eq!(dfu.state(), State::DfuIdle);
+        assert_eq!(dfu.get_status()[0], Status::Ok as u8);
+    }
+
+    #[test]
+    fn image_too_large() {
+        let alloc = UsbBusAllocator::new(MockBus);
+        let mut dfu = DfuClass::new(&alloc, Flash::new(), &CONFIG);
+        let max = CONFIG.max_image_size::<Flash>() as usize;
+        for _ in 0..max / TRANSFER_SIZE {
+            let status = download(&mut dfu, &[0x55; TRANSFER_SIZE]);
+            assert_eq!(status[0], Status::Ok as u8);
+        }
+        let status = download(&mut dfu, &[0x55; TRANSFER_SIZE]);
+        assert_eq!(status[0], Status::ErrAddress as u8);
+        assert_eq!(status[4], State::Error as u8);
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let alloc = UsbBusAllocator::new(MockBus);
+        let mut dfu = DfuClass::new(&alloc, Flash::new(), &CONFIG);
+        // manifestation without a download
+        assert!(!dfu.request_out(DFU_DNLOAD, &[]));
+        assert_eq!(dfu.get_status()[0], Status::ErrStalledPkt as u8);
+        assert!(dfu.request_out(DFU_CLRSTATUS, &[]));
+        // block larger than wTransferSize
+        assert!(!dfu.request_out(DFU_DNLOAD, &[0; TRANSFER_SIZE + 1]));
+        assert_eq!(dfu.state(), State::Error);
+        assert!(dfu.request_out(DFU_CLRSTATUS, &[]));
+        // DFU_CLRSTATUS outside of the error state
+        assert!(!dfu.request_out(DFU_CLRSTATUS, &[]));
+        assert_eq!(dfu.state(), State::Error);
+    }
+}
@@ -0,0 +1,174 @@
+//! Application image validation
+//!
+//! The last erase page of the application region holds an `ImageInfo` record
+//! with the length and the CRC-32 of the application image. The record is
+//! written after the image has been programmed and verified and is erased
+//! before a new image is downloaded so that an incomplete download is never
+//! started.
+
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+use crate::Config;
+
+pub use pic32_crc::Crc32;
+
+/// Marks a valid `ImageInfo` record
+const IMAGE_MAGIC: u32 = 0x4946_4e49;
+
+/// Size of the buffer used to read the image when calculating the CRC
+const READ_CHUNK_SIZE: usize = 64;
+
+/// Image information stored behind the application image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// Length of the image in bytes
+    pub length: u32,
+    /// CRC-32 of the image
+    pub crc: u32,
+}
+
+impl ImageInfo {
+    /// Size of the serialized record
+    pub const SIZE: usize = 16;
+
+    /// Serialize the record
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0xff; Self::SIZE];
+        bytes[0..4].copy_from_slice(&IMAGE_MAGIC.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.length.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.crc.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a record; returns `None` if there is no valid record
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let word = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        if word(0) != IMAGE_MAGIC {
+            return None;
+        }
+        Some(ImageInfo {
+            length: word(4),
+            crc: word(8),
+        })
+    }
+
+    /// Read the record from the flash memory
+    pub fn read<F: NorFlash>(flash: &mut F, config: &Config) -> Option<Self> {
+        let mut bytes = [0; Self::SIZE];
+        flash.read(config.info_offset::<F>(), &mut bytes).ok()?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Calculate the CRC-32 of `length` bytes of the application region
+pub fn image_crc<F: ReadNorFlash>(
+    flash: &mut F,
+    config: &Config,
+    length: u32,
+) -> Result<u32, F::Error> {
+    let mut crc = Crc32::new();
+    let mut buf = [0; READ_CHUNK_SIZE];
+    let mut offset = 0;
+    while offset < length {
+        let len = READ_CHUNK_SIZE.min((length - offset) as usize);
+        flash.read(config.app_offset + offset, &mut buf[..len])?;
+        crc.update(&buf[..len]);
+        offset += len as u32;
+    }
+    Ok(crc.finish())
+}
+
+/// Check if the application region contains a complete image with a matching
+/// CRC
+pub fn validate<F: NorFlash>(flash: &mut F, config: &Config) -> Option<ImageInfo> {
+    let info = ImageInfo::read(flash, config)?;
+    if info.length == 0 || info.length > config.max_image_size::<F>() {
+        return None;
+    }
+    match image_crc(flash, config, info.length) {
+        Ok(crc) if crc == info.crc => Some(info),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockFlash;
+    use embedded_storage::nor_flash::NorFlash;
+
+    type Flash = MockFlash;
+
+    const CONFIG: Config = Config {
+        vid: 0x16c0,
+        pid: 0x27dd,
+        manufacturer: "Test",
+        product: "Test",
+        app_offset: 1024,
+        app_size: 3072,
+        app_entry: 0x9d00_0400,
+    };
+
+    #[test]
+    fn crc_check_value() {
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn image_info_round_trip() {
+        let info = ImageInfo {
+            length: 0x1234,
+            crc: 0xcbf4_3926,
+        };
+        let bytes = info.to_bytes();
+        assert_eq!(ImageInfo::from_bytes(&bytes), Some(info));
+        assert_eq!(ImageInfo::from_bytes(&[0xff; ImageInfo::SIZE]), None);
+        let mut corrupted = bytes;
+        corrupted[0] ^= 1;
+        assert_eq!(ImageInfo::from_bytes(&corrupted), None);
+    }
+
+    #[test]
+    fn validate_image() {
+        let mut flash = Flash::new();
+        assert_eq!(validate(&mut flash, &CONFIG), None);
+
+        flash
+            .write(CONFIG.app_offset, b"123456789\xff\xff\xff")
+            .unwrap();
+        let info = ImageInfo {
+            length: 9,
+            crc: 0xcbf4_3926,
+        };
+        flash
+            .write(CONFIG.info_offset::<Flash>(), &info.to_bytes())
+            .unwrap();
+        assert_eq!(ImageInfo::read(&mut flash, &CONFIG), Some(info));
+        assert_eq!(validate(&mut flash, &CONFIG), Some(info));
+
+        // CRC mismatch
+        let mut flash = Flash::new();
+        flash
+            .write(CONFIG.app_offset, b"123456780\xff\xff\xff")
+            .unwrap();
+        flash
+            .write(CONFIG.info_offset::<Flash>(), &info.to_bytes())
+            .unwrap();
+        assert_eq!(validate(&mut flash, &CONFIG), None);
+    }
+
+    #[test]
+    fn reject_oversized_length() {
+        let mut flash = Flash::new();
+        let info = ImageInfo {
+            length: CONFIG.max_image_size::<Flash>() + 1,
+            crc: 0,
+        };
+        flash
+            .write(CONFIG.info_offset::<Flash>(), &info.to_bytes())
+            .unwrap();
+        assert_eq!(validate(&mut flash, &CONFIG), None);
+    }
+}
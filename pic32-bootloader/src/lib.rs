@@ -0,0 +1,155 @@
+//! USB DFU bootloader for PIC32 microcontrollers
+//!
+//! This crate implements a bootloader according to the USB Device Firmware
+//! Upgrade (DFU) specification 1.1. The application image is downloaded into
+//! an application region of the flash memory, which is accessed by means of
+//! the `NorFlash` trait of the `embedded-storage` crate. The last erase page
+//! of the application region holds the length and the CRC-32 of the image
+//! (see the `image` module). The bootloader starts the application if the
+//! image is valid and the application did not request the bootloader.
+//!
+//! The application requests the bootloader with `reboot_to_bootloader()`,
+//! e.g. when the `DfuRuntimeClass` receives a `DFU_DETACH` request. The request
+//! is passed via a RAM word at the address of the linker symbol
+//! `_bootloader_request`, which must be defined by the `memory.x` files of both
+//! the bootloader and the application and must be excluded from the RAM
+//! memory region (see README).
+//!
+//! The functions accessing the hardware require one of the device features.
+//! Without a device feature, the `dfu` and `image` modules can be tested on
+//! the host.
+
+#![no_std]
+
+pub mod dfu;
+pub mod image;
+#[cfg(test)]
+mod mock;
+
+#[cfg(feature = "device-selected")]
+use core::ptr::{addr_of_mut, read_volatile, write_volatile};
+
+use embedded_storage::nor_flash::NorFlash;
+#[cfg(feature = "device-selected")]
+use pic32_hal::{
+    pac::USB,
+    reset::software_reset,
+    usb::{UsbBus, UsbMemory},
+};
+#[cfg(feature = "device-selected")]
+use usb_device::prelude::*;
+
+#[cfg(feature = "device-selected")]
+use dfu::DfuClass;
+
+/// Value of the request word requesting the bootloader
+#[cfg(feature = "device-selected")]
+const REQUEST_MAGIC: u32 = 0x4446_5552;
+
+#[cfg(feature = "device-selected")]
+extern "C" {
+    /// RAM word not initialized by the startup code
+    static mut _bootloader_request: u32;
+}
+
+/// Bootloader configuration
+pub struct Config {
+    /// USB vendor ID
+    pub vid: u16,
+    /// USB product ID
+    pub pid: u16,
+    pub manufacturer: &'static str,
+    pub product: &'static str,
+    /// Offset of the application region within the flash memory accessed via
+    /// `NorFlash`. Must be a multiple of the erase size.
+    pub app_offset: u32,
+    /// Size of the application region including the erase page reserved for
+    /// the image information. Must be a multiple of the erase size.
+    pub app_size: u32,
+    /// Virtual address of the reset handler of the application
+    pub app_entry: usize,
+}
+
+impl Config {
+    /// Offset of the `ImageInfo` record
+    pub(crate) fn info_offset<F: NorFlash>(&self) -> u32 {
+        self.app_offset + self.max_image_size::<F>()
+    }
+
+    /// Maximum size of an application image
+    pub(crate) fn max_image_size<F: NorFlash>(&self) -> u32 {
+        self.app_size - F::ERASE_SIZE as u32
+    }
+}
+
+/// Reset the device and stay in the bootloader
+///
+/// To be called by the application.
+#[cfg(feature = "device-selected")]
+pub fn reboot_to_bootloader() -> ! {
+    unsafe { write_volatile(addr_of_mut!(_bootloader_request), REQUEST_MAGIC) };
+    software_reset()
+}
+
+/// Check and clear the bootloader request of the application
+#[cfg(feature = "device-selected")]
+pub fn take_request() -> bool {
+    unsafe {
+        let request = addr_of_mut!(_bootloader_request);
+        let requested = read_volatile(request) == REQUEST_MAGIC;
+        write_volatile(request, 0);
+        requested
+    }
+}
+
+/// Jump to the reset handler of the application
+///
+/// # Safety
+///
+/// The application region must contain a valid image and all peripherals used
+/// by the bootloader must be in their reset state.
+#[cfg(feature = "device-selected")]
+pub unsafe fn start_application(config: &Config) -> ! {
+    mips_mcu::interrupt::disable();
+    let entry: extern "C" fn() -> ! = core::mem::transmute(config.app_entry);
+    entry()
+}
+
+/// Start the application unless the application requested the bootloader or
+/// the image is not valid
+///
+/// Returns if the bootloader is to be run. To be called before initializing
+/// any peripherals.
+#[cfg(feature = "device-selected")]
+pub fn try_start_application<F: NorFlash>(flash: &mut F, config: &Config) {
+    if !take_request() && image::validate(flash, config).is_some() {
+        unsafe { start_application(config) }
+    }
+}
+
+/// Run the DFU mode
+///
+/// The USB clock must be configured. Resets the device after a new image has
+/// been downloaded and the host reset the bus.
+#[cfg(feature = "device-selected")]
+pub fn run<F: NorFlash, const N: usize>(
+    usb: USB,
+    memory: &'static mut UsbMemory<N>,
+    flash: F,
+    config: &Config,
+) -> ! {
+    let usb_bus = UsbBus::with_memory(usb, memory);
+    let mut dfu = DfuClass::new(&usb_bus, flash, config);
+    let mut device = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(config.vid, config.pid))
+        .strings(&[StringDescriptors::default()
+            .manufacturer(config.manufacturer)
+            .product(config.product)])
+        .unwrap()
+        .build();
+    loop {
+        device.poll(&mut [&mut dfu]);
+        if dfu.reboot_requested() {
+            software_reset();
+        }
+    }
+}
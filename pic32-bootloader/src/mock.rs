@@ -0,0 +1,88 @@
+//! Flash memory mock for testing on the host
+
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Error of the mock flash memory
+#[derive(Debug)]
+pub struct MockError(NorFlashErrorKind);
+
+impl NorFlashError for MockError {
+    fn kind(&self) -> NorFlashErrorKind {
+        self.0
+    }
+}
+
+/// Flash memory of 4 erase pages of 1 KiB with word programming
+///
+/// Like a real NOR flash, programming can only clear bits.
+pub struct MockFlash {
+    data: [u8; MockFlash::SIZE],
+}
+
+impl MockFlash {
+    const SIZE: usize = 4096;
+
+    /// Create an erased flash memory
+    pub fn new() -> Self {
+        MockFlash {
+            data: [0xff; Self::SIZE],
+        }
+    }
+
+    /// Contents of the flash memory
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn check(&self, offset: u32, len: usize, align: usize) -> Result<usize, MockError> {
+        let offset = offset as usize;
+        if !offset.is_multiple_of(align) || !len.is_multiple_of(align) {
+            return Err(MockError(NorFlashErrorKind::NotAligned));
+        }
+        if offset + len > Self::SIZE {
+            return Err(MockError(NorFlashErrorKind::OutOfBounds));
+        }
+        Ok(offset)
+    }
+}
+
+impl ErrorType for MockFlash {
+    type Error = MockError;
+}
+
+impl ReadNorFlash for MockFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), MockError> {
+        let offset = self.check(offset, bytes.len(), Self::READ_SIZE)?;
+        bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        Self::SIZE
+    }
+}
+
+impl NorFlash for MockFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = 1024;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), MockError> {
+        let from = self.check(from, 0, Self::ERASE_SIZE)?;
+        let len = (to as usize).saturating_sub(from);
+        self.check(from as u32, len, Self::ERASE_SIZE)?;
+        self.data[from..from + len].fill(0xff);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), MockError> {
+        let offset = self.check(offset, bytes.len(), Self::WRITE_SIZE)?;
+        for (cell, byte) in self.data[offset..].iter_mut().zip(bytes) {
+            *cell &= byte;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+//! CRC-32 calculation without external dependencies
+//!
+//! The CRC is calculated bitwise without a lookup table to keep the code
+//! small, which matters for the bootloader.
+
+#![no_std]
+
+/// CRC-32 (IEEE 802.3) calculation
+///
+/// Reflected polynomial 0xedb88320, initial value and final XOR 0xffffffff.
+/// The check value for the ASCII string "123456789" is 0xcbf43926.
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    /// Start a new CRC calculation
+    pub const fn new() -> Self {
+        Crc32(0xffff_ffff)
+    }
+
+    /// Process bytes
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xedb8_8320 & mask);
+            }
+        }
+    }
+
+    /// Get the CRC value of the bytes processed so far
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xcbf4_3926);
+        assert_eq!(Crc32::default().finish(), 0);
+    }
+}
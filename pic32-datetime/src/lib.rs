@@ -0,0 +1,285 @@
+//! Calendar date and time without external dependencies
+//!
+//! `DateTime` covers the range supported by the RTCC, i.e. the years 2000 to
+//! 2099. It does not depend on any hardware and can be converted from and to a
+//! Unix timestamp.
+
+#![no_std]
+
+/// Unix timestamp of 2000-01-01 00:00:00
+const UNIX_2000: u32 = 946_684_800;
+
+/// Unix timestamp of 2100-01-01 00:00:00
+const UNIX_2100: u32 = 4_102_444_800;
+
+const SECONDS_PER_DAY: u32 = 86_400;
+
+/// Day of the week
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum Weekday {
+    Sunday = 0,
+    Monday = 1,
+    Tuesday = 2,
+    Wednesday = 3,
+    Thursday = 4,
+    Friday = 5,
+    Saturday = 6,
+}
+
+impl Weekday {
+    /// Create from the number of the day counted from Sunday (0) to Saturday
+    /// (6)
+    pub fn from_number(n: u8) -> Option<Self> {
+        match n {
+            0 => Some(Weekday::Sunday),
+            1 => Some(Weekday::Monday),
+            2 => Some(Weekday::Tuesday),
+            3 => Some(Weekday::Wednesday),
+            4 => Some(Weekday::Thursday),
+            5 => Some(Weekday::Friday),
+            6 => Some(Weekday::Saturday),
+            _ => None,
+        }
+    }
+}
+
+/// Check if `year` is a leap year
+pub const fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+/// Number of days of a month (1 ... 12)
+pub const fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Calendar date and time between 2000-01-01 00:00:00 and 2099-12-31 23:59:59
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct DateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl DateTime {
+    /// Create a `DateTime` from its components
+    ///
+    /// Returns `None` if any component is out of range.
+    pub const fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Option<Self> {
+        if year < 2000
+            || year > 2099
+            || month < 1
+            || month > 12
+            || day < 1
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return None;
+        }
+        Some(DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+
+    /// Year (2000 ... 2099)
+    pub const fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// Month (1 ... 12)
+    pub const fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// Day of the month (1 ... 31)
+    pub const fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// Hour (0 ... 23)
+    pub const fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// Minute (0 ... 59)
+    pub const fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// Second (0 ... 59)
+    pub const fn second(&self) -> u8 {
+        self.second
+    }
+
+    /// Number of days since 2000-01-01
+    const fn days_since_2000(&self) -> u32 {
+        let years = (self.year - 2000) as u32;
+        // 2000 is a leap year and there is no exception to the 4-year rule
+        // until 2100
+        let mut days = years * 365 + years.div_ceil(4);
+        let mut month = 1;
+        while month < self.month {
+            days += days_in_month(self.year, month) as u32;
+            month += 1;
+        }
+        days + self.day as u32 - 1
+    }
+
+    /// Day of the week
+    pub const fn weekday(&self) -> Weekday {
+        // 2000-01-01 was a Saturday
+        match (self.days_since_2000() + 6) % 7 {
+            0 => Weekday::Sunday,
+            1 => Weekday::Monday,
+            2 => Weekday::Tuesday,
+            3 => Weekday::Wednesday,
+            4 => Weekday::Thursday,
+            5 => Weekday::Friday,
+            _ => Weekday::Saturday,
+        }
+    }
+
+    /// Convert to a Unix timestamp (seconds since 1970-01-01 00:00:00 UTC
+    /// assuming that the `DateTime` is in UTC)
+    pub const fn to_unix_timestamp(&self) -> u32 {
+        UNIX_2000
+            + self.days_since_2000() * SECONDS_PER_DAY
+            + self.hour as u32 * 3600
+            + self.minute as u32 * 60
+            + self.second as u32
+    }
+
+    /// Create a `DateTime` from a Unix timestamp
+    ///
+    /// Returns `None` if the timestamp is outside the range supported by
+    /// `DateTime`.
+    pub const fn from_unix_timestamp(timestamp: u32) -> Option<Self> {
+        if timestamp < UNIX_2000 || timestamp >= UNIX_2100 {
+            return None;
+        }
+        let secs = timestamp - UNIX_2000;
+        let mut days = secs / SECONDS_PER_DAY;
+        let secs_of_day = secs % SECONDS_PER_DAY;
+
+        let mut year = 2000;
+        loop {
+            let days_in_year = if is_leap_year(year) { 366 } else { 365 };
+            if days < days_in_year {
+                break;
+            }
+            days -= days_in_year;
+            year += 1;
+        }
+        let mut month = 1;
+        loop {
+            let dim = days_in_month(year, month) as u32;
+            if days < dim {
+                break;
+            }
+            days -= dim;
+            month += 1;
+        }
+        Some(DateTime {
+            year,
+            month,
+            day: days as u8 + 1,
+            hour: (secs_of_day / 3600) as u8,
+            minute: (secs_of_day / 60 % 60) as u8,
+            second: (secs_of_day % 60) as u8,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leap_years() {
+        assert!(is_leap_year(2000));
+        assert!(is_leap_year(2024));
+        assert!(!is_leap_year(2023));
+        assert!(!is_leap_year(2100));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2023, 2), 28);
+        assert_eq!(days_in_month(2023, 4), 30);
+        assert_eq!(days_in_month(2023, 12), 31);
+        assert!(DateTime::new(2024, 2, 29, 0, 0, 0).is_some());
+        assert!(DateTime::new(2023, 2, 29, 0, 0, 0).is_none());
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert!(DateTime::new(1999, 12, 31, 23, 59, 59).is_none());
+        assert!(DateTime::new(2100, 1, 1, 0, 0, 0).is_none());
+        assert!(DateTime::new(2023, 13, 1, 0, 0, 0).is_none());
+        assert!(DateTime::new(2023, 1, 0, 0, 0, 0).is_none());
+        assert!(DateTime::new(2023, 1, 1, 24, 0, 0).is_none());
+        assert!(DateTime::new(2023, 1, 1, 0, 60, 0).is_none());
+        assert!(DateTime::new(2023, 1, 1, 0, 0, 60).is_none());
+    }
+
+    #[test]
+    fn weekdays() {
+        let weekday = |y, m, d| DateTime::new(y, m, d, 12, 0, 0).unwrap().weekday();
+        assert_eq!(weekday(2000, 1, 1), Weekday::Saturday);
+        assert_eq!(weekday(2000, 2, 29), Weekday::Tuesday);
+        assert_eq!(weekday(2000, 3, 1), Weekday::Wednesday);
+        assert_eq!(weekday(2001, 1, 1), Weekday::Monday);
+        assert_eq!(weekday(2024, 2, 29), Weekday::Thursday);
+        assert_eq!(weekday(2099, 12, 31), Weekday::Thursday);
+        assert_eq!(Weekday::from_number(3), Some(Weekday::Wednesday));
+        assert_eq!(Weekday::from_number(7), None);
+    }
+
+    #[test]
+    fn unix_timestamps() {
+        let cases = [
+            ((2000, 1, 1, 0, 0, 0), 946_684_800),
+            ((2000, 3, 1, 0, 0, 0), 951_868_800),
+            ((2001, 1, 1, 0, 0, 0), 978_307_200),
+            ((2021, 7, 4, 13, 37, 42), 1_625_405_862),
+            ((2024, 2, 29, 23, 59, 59), 1_709_251_199),
+            ((2038, 1, 19, 3, 14, 8), 2_147_483_648),
+            ((2099, 12, 31, 23, 59, 59), 4_102_444_799),
+        ];
+        for ((y, mo, d, h, mi, s), timestamp) in cases {
+            let datetime = DateTime::new(y, mo, d, h, mi, s).unwrap();
+            assert_eq!(datetime.to_unix_timestamp(), timestamp);
+            assert_eq!(DateTime::from_unix_timestamp(timestamp), Some(datetime));
+        }
+        assert_eq!(DateTime::from_unix_timestamp(946_684_799), None);
+        assert_eq!(DateTime::from_unix_timestamp(4_102_444_800), None);
+    }
+
+    #[test]
+    fn unix_timestamp_round_trip() {
+        for timestamp in (946_684_800..4_102_444_800).step_by(86_400 * 7 + 3_607) {
+            let datetime = DateTime::from_unix_timestamp(timestamp).unwrap();
+            assert_eq!(datetime.to_unix_timestamp(), timestamp);
+        }
+    }
+}
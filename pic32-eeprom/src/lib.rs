@@ -0,0 +1,474 @@
+//! Wear-levelled EEPROM emulation in flash memory
+//!
+//! PIC32MX devices have no data EEPROM. This crate stores key-value records in
+//! a number of flash pages (erase units) accessed by means of the `NorFlash`
+//! trait of the `embedded-storage` crate, e.g. `pic32_hal::nvm::Nvm`.
+//!
+//! The pages are used as a ring. Exactly one page is active. New records are
+//! appended to the active page. Each record carries a sequence number and a
+//! CRC-32 so that a record that has been interrupted by a power failure is
+//! detected and ignored. When the active page is full, the latest record of
+//! each key is copied to the next page (garbage collection) before the new
+//! record is written. The header of the next page, which makes it the active
+//! page, is written last. Until then, the previous page remains valid so that
+//! a power failure during a page swap does not lose any data. Moving through
+//! the pages as a ring distributes the erase cycles evenly.
+//!
+//! The `sim` module provides a simulated flash memory with power failure
+//! injection for testing on the host.
+//!
+//! ```ignore
+//! let mut eeprom = Eeprom::new(Nvm::new(p.NVM), 0x1c000, 4)?;
+//! eeprom.write(1, &baudrate.to_le_bytes())?;
+//! let mut buf = [0; 4];
+//! if let Some(len) = eeprom.read(1, &mut buf)? { /* ... */ }
+//! ```
+
+#![no_std]
+
+pub mod sim;
+
+use embedded_storage::nor_flash::NorFlash;
+use pic32_crc::Crc32;
+
+/// Marks a page header
+const PAGE_MAGIC: u32 = 0x5045_4550;
+
+/// Size of the page header: magic, sequence number, inverted sequence number,
+/// reserved word
+const PAGE_HEADER_SIZE: usize = 16;
+
+/// Size of the record header: key, length, sequence number, CRC
+const RECORD_HEADER_SIZE: usize = 12;
+
+/// Flag in the length field marking a removed key
+const DELETED: u16 = 0x8000;
+
+/// Key of an erased record header
+const ERASED_KEY: u16 = 0xffff;
+
+/// Size of the buffer used to copy and check records
+const CHUNK_SIZE: usize = 64;
+
+/// EEPROM emulation errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Flash memory error
+    Flash(E),
+    /// The page count is less than 2 or the pages are not within the flash
+    /// memory or not aligned to erase pages
+    InvalidConfig,
+    /// The key 0xffff is reserved
+    InvalidKey,
+    /// The value does not fit into a page
+    TooLarge,
+    /// The value does not fit into a page together with all other values
+    Full,
+    /// The buffer is too small for the value
+    BufferTooSmall,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Error::Flash(e)
+    }
+}
+
+/// Record located in a page
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    /// Offset relative to the beginning of the page
+    offset: u32,
+    key: u16,
+    len: usize,
+    deleted: bool,
+    seq: u32,
+    /// Size including header and padding
+    size: u32,
+}
+
+/// Result of scanning a page at a given offset
+enum Scan {
+    Record(Record),
+    /// No further records
+    Free,
+    /// Invalid record, e.g. interrupted by a power failure
+    Corrupt,
+}
+
+/// EEPROM emulation
+pub struct Eeprom<F: NorFlash> {
+    flash: F,
+    /// Offset of the first page within the flash memory
+    offset: u32,
+    pages: usize,
+    active: usize,
+    page_seq: u32,
+    record_seq: u32,
+    /// Offset of the next record within the active page
+    write_offset: u32,
+}
+
+impl<F: NorFlash> Eeprom<F> {
+    /// Size of a page
+    const PAGE_SIZE: u32 = F::ERASE_SIZE as u32;
+
+    /// Create a new EEPROM emulation using `pages` erase pages of `flash`
+    /// starting at `offset`
+    ///
+    /// The state is restored from the flash memory. If there is no valid page,
+    /// the pages are formatted.
+    ///
+    /// `F::READ_SIZE` must be 1 and `F::WRITE_SIZE` must not exceed 16 bytes.
+    pub fn new(flash: F, offset: u32, pages: usize) -> Result<Self, Error<F::Error>> {
+        assert!(F::READ_SIZE == 1);
+        assert!(PAGE_HEADER_SIZE.is_multiple_of(F::WRITE_SIZE));
+        if pages < 2
+            || !(offset as usize).is_multiple_of(F::ERASE_SIZE)
+            || offset as usize + pages * F::ERASE_SIZE > flash.capacity()
+        {
+            return Err(Error::InvalidConfig);
+        }
+        let mut eeprom = Eeprom {
+            flash,
+            offset,
+            pages,
+            active: 0,
+            page_seq: 0,
+            record_seq: 0,
+            write_offset: 0,
+        };
+        eeprom.mount()?;
+        Ok(eeprom)
+    }
+
+    /// Return the flash memory driver
+    pub fn free(self) -> F {
+        self.flash
+    }
+
+    /// Erase all pages and remove all values
+    pub fn format(&mut self) -> Result<(), Error<F::Error>> {
+        for page in 0..self.pages {
+            self.erase_page(page)?;
+        }
+        self.write_page_header(0, 1)?;
+        self.active = 0;
+        self.page_seq = 1;
+        self.record_seq = 0;
+        self.write_offset = PAGE_HEADER_SIZE as u32;
+        Ok(())
+    }
+
+    /// Read the value of `key` into `buf`
+    ///
+    /// Returns the length of the value or `None` if there is no value.
+    pub fn read(&mut self, key: u16, buf: &mut [u8]) -> Result<Option<usize>, Error<F::Error>> {
+        if key == ERASED_KEY {
+            return Err(Error::InvalidKey);
+        }
+        let record = match self.find(self.active, key, PAGE_HEADER_SIZE as u32)? {
+            Some(record) if !record.deleted => record,
+            _ => return Ok(None),
+        };
+        if buf.len() < record.len {
+            return Err(Error::BufferTooSmall);
+        }
+        let addr = self.page_addr(self.active) + record.offset + RECORD_HEADER_SIZE as u32;
+        self.flash.read(addr, &mut buf[..record.len])?;
+        Ok(Some(record.len))
+    }
+
+    /// Write the value of `key`
+    pub fn write(&mut self, key: u16, data: &[u8]) -> Result<(), Error<F::Error>> {
+        self.append(key, data, false)
+    }
+
+    /// Remove the value of `key`
+    pub fn remove(&mut self, key: u16) -> Result<(), Error<F::Error>> {
+        if key == ERASED_KEY {
+            return Err(Error::InvalidKey);
+        }
+        match self.find(self.active, key, PAGE_HEADER_SIZE as u32)? {
+            Some(record) if !record.deleted => self.append(key, &[], true),
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of bytes left in the active page
+    ///
+    /// More space may become available by garbage collection.
+    pub fn free_space(&self) -> usize {
+        (Self::PAGE_SIZE - self.write_offset) as usize
+    }
+
+    fn page_addr(&self, page: usize) -> u32 {
+        self.offset + page as u32 * Self::PAGE_SIZE
+    }
+
+    /// Size of a record including header and padding
+    fn record_size(len: usize) -> u32 {
+        (RECORD_HEADER_SIZE + len).next_multiple_of(F::WRITE_SIZE) as u32
+    }
+
+    fn erase_page(&mut self, page: usize) -> Result<(), Error<F::Error>> {
+        let addr = self.page_addr(page);
+        self.flash.erase(addr, addr + Self::PAGE_SIZE)?;
+        Ok(())
+    }
+
+    /// Read the page header and return the sequence number of a valid page
+    fn read_page_header(&mut self, page: usize) -> Result<Option<u32>, Error<F::Error>> {
+        let mut header = [0; PAGE_HEADER_SIZE];
+        self.flash.read(self.page_addr(page), &mut header)?;
+        let word = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        if word(0) == PAGE_MAGIC && word(8) == !word(4) {
+            Ok(Some(word(4)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn write_page_header(&mut self, page: usize, seq: u32) -> Result<(), Error<F::Error>> {
+        let mut header = [0xff; PAGE_HEADER_SIZE];
+        header[0..4].copy_from_slice(&PAGE_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&seq.to_le_bytes());
+        header[8..12].copy_from_slice(&(!seq).to_le_bytes());
+        self.flash.write(self.page_addr(page), &header)?;
+        Ok(())
+    }
+
+    /// Find the active page and the end of its records
+    fn mount(&mut self) -> Result<(), Error<F::Error>> {
+        let mut active: Option<(usize, u32)> = None;
+        for page in 0..self.pages {
+            if let Some(seq) = self.read_page_header(page)? {
+                if active.is_none_or(|(_, s)| seq > s) {
+                    active = Some((page, seq));
+                }
+            }
+        }
+        let Some((page, seq)) = active else {
+            return self.format();
+        };
+        self.active = page;
+        self.page_seq = seq;
+        self.record_seq = 0;
+        let mut offset = PAGE_HEADER_SIZE as u32;
+        loop {
+            match self.scan(page, offset)? {
+                Scan::Record(record) => {
+                    self.record_seq = self.record_seq.max(record.seq);
+                    offset += record.size;
+                }
+                Scan::Free if self.is_erased(page, offset)? => {
+                    self.write_offset = offset;
+                    break;
+                }
+                // do not append to a page that contains partially written data
+                Scan::Free | Scan::Corrupt => {
+                    self.write_offset = Self::PAGE_SIZE;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if the page is erased from `offset` to its end
+    fn is_erased(&mut self, page: usize, offset: u32) -> Result<bool, Error<F::Error>> {
+        let mut buf = [0; CHUNK_SIZE];
+        let mut pos = offset;
+        while pos < Self::PAGE_SIZE {
+            let len = CHUNK_SIZE.min((Self::PAGE_SIZE - pos) as usize);
+            self.flash
+                .read(self.page_addr(page) + pos, &mut buf[..len])?;
+            if buf[..len].iter().any(|b| *b != 0xff) {
+                return Ok(false);
+            }
+            pos += len as u32;
+        }
+        Ok(true)
+    }
+
+    /// Read and check the record at `offset`
+    fn scan(&mut self, page: usize, offset: u32) -> Result<Scan, Error<F::Error>> {
+        if offset as usize + RECORD_HEADER_SIZE > F::ERASE_SIZE {
+            return Ok(Scan::Free);
+        }
+        let addr = self.page_addr(page) + offset;
+        let mut header = [0; RECORD_HEADER_SIZE];
+        self.flash.read(addr, &mut header)?;
+        if header.iter().all(|b| *b == 0xff) {
+            return Ok(Scan::Free);
+        }
+        let key = u16::from_le_bytes([header[0], header[1]]);
+        let len_flags = u16::from_le_bytes([header[2], header[3]]);
+        let seq = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let crc = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let len = (len_flags & !DELETED) as usize;
+        let size = Self::record_size(len);
+        if key == ERASED_KEY || offset + size > Self::PAGE_SIZE {
+            return Ok(Scan::Corrupt);
+        }
+        let mut calc = Crc32::new();
+        calc.update(&header[..8]);
+        let mut buf = [0; CHUNK_SIZE];
+        let mut pos = 0;
+        while pos < len {
+            let n = CHUNK_SIZE.min(len - pos);
+            let data_addr = addr + (RECORD_HEADER_SIZE + pos) as u32;
+            self.flash.read(data_addr, &mut buf[..n])?;
+            calc.update(&buf[..n]);
+            pos += n;
+        }
+        if calc.finish() != crc {
+            return Ok(Scan::Corrupt);
+        }
+        Ok(Scan::Record(Record {
+            offset,
+            key,
+            len,
+            deleted: len_flags & DELETED != 0,
+            seq,
+            size,
+        }))
+    }
+
+    /// Find the last record of `key` starting at `offset`
+    fn find(
+        &mut self,
+        page: usize,
+        key: u16,
+        offset: u32,
+    ) -> Result<Option<Record>, Error<F::Error>> {
+        let mut found = None;
+        let mut offset = offset;
+        while let Scan::Record(record) = self.scan(page, offset)? {
+            if record.key == key {
+                found = Some(record);
+            }
+            offset += record.size;
+        }
+        Ok(found)
+    }
+
+    /// Write a record at `offset`
+    fn write_record(
+        &mut self,
+        page: usize,
+        offset: u32,
+        key: u16,
+        data: &[u8],
+        deleted: bool,
+        seq: u32,
+    ) -> Result<u32, Error<F::Error>> {
+        let len_flags = data.len() as u16 | if deleted { DELETED } else { 0 };
+        let mut header = [0; RECORD_HEADER_SIZE];
+        header[0..2].copy_from_slice(&key.to_le_bytes());
+        header[2..4].copy_from_slice(&len_flags.to_le_bytes());
+        header[4..8].copy_from_slice(&seq.to_le_bytes());
+        let mut crc = Crc32::new();
+        crc.update(&header[..8]);
+        crc.update(data);
+        header[8..12].copy_from_slice(&crc.finish().to_le_bytes());
+
+        let size = Self::record_size(data.len());
+        let addr = self.page_addr(page) + offset;
+        let mut src = header.iter().chain(data).copied();
+        let mut chunk = [0; CHUNK_SIZE];
+        let mut pos = 0;
+        while pos < size {
+            let n = CHUNK_SIZE.min((size - pos) as usize);
+            for byte in chunk[..n].iter_mut() {
+                *byte = src.next().unwrap_or(0xff);
+            }
+            self.flash.write(addr + pos, &chunk[..n])?;
+            pos += n as u32;
+        }
+        Ok(size)
+    }
+
+    /// Copy a record to another page
+    fn copy_record(
+        &mut self,
+        record: &Record,
+        page: usize,
+        offset: u32,
+    ) -> Result<(), Error<F::Error>> {
+        let src = self.page_addr(self.active) + record.offset;
+        let dst = self.page_addr(page) + offset;
+        let mut chunk = [0; CHUNK_SIZE];
+        let mut pos = 0;
+        while pos < record.size {
+            let n = CHUNK_SIZE.min((record.size - pos) as usize);
+            self.flash.read(src + pos, &mut chunk[..n])?;
+            self.flash.write(dst + pos, &chunk[..n])?;
+            pos += n as u32;
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, key: u16, data: &[u8], deleted: bool) -> Result<(), Error<F::Error>> {
+        if key == ERASED_KEY {
+            return Err(Error::InvalidKey);
+        }
+        let size = Self::record_size(data.len());
+        if data.len() >= DELETED as usize || PAGE_HEADER_SIZE as u32 + size > Self::PAGE_SIZE {
+            return Err(Error::TooLarge);
+        }
+        if self.write_offset + size > Self::PAGE_SIZE {
+            return self.swap(key, data, deleted);
+        }
+        let seq = self.record_seq.wrapping_add(1);
+        match self.write_record(self.active, self.write_offset, key, data, deleted, seq) {
+            Ok(size) => {
+                self.record_seq = seq;
+                self.write_offset += size;
+                Ok(())
+            }
+            Err(e) => {
+                // the page may contain a partially written record
+                self.write_offset = Self::PAGE_SIZE;
+                Err(e)
+            }
+        }
+    }
+
+    /// Copy the latest records to the next page, append the new record and
+    /// make the next page the active page
+    fn swap(&mut self, key: u16, data: &[u8], deleted: bool) -> Result<(), Error<F::Error>> {
+        let next = (self.active + 1) % self.pages;
+        self.erase_page(next)?;
+        let mut dst = PAGE_HEADER_SIZE as u32;
+        let mut offset = PAGE_HEADER_SIZE as u32;
+        while let Scan::Record(record) = self.scan(self.active, offset)? {
+            offset += record.size;
+            if record.key == key
+                || record.deleted
+                || self.find(self.active, record.key, offset)?.is_some()
+            {
+                continue;
+            }
+            if dst + record.size > Self::PAGE_SIZE {
+                return Err(Error::Full);
+            }
+            self.copy_record(&record, next, dst)?;
+            dst += record.size;
+        }
+        let mut record_seq = self.record_seq;
+        if !deleted {
+            if dst + Self::record_size(data.len()) > Self::PAGE_SIZE {
+                return Err(Error::Full);
+            }
+            record_seq = record_seq.wrapping_add(1);
+            dst += self.write_record(next, dst, key, data, false, record_seq)?;
+        }
+        self.write_page_header(next, self.page_seq.wrapping_add(1))?;
+        self.active = next;
+        self.page_seq = self.page_seq.wrapping_add(1);
+        self.record_seq = record_seq;
+        self.write_offset = dst;
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! Simulated flash memory for testing on the host
+//!
+//! `SimFlash` behaves like a NOR flash memory: erasing sets all bytes of a
+//! page to 0xff and bytes can only be programmed when erased. A power failure
+//! can be injected after a given number of byte operations (each programmed
+//! or erased byte counts as one operation). The interrupted operation is then
+//! left incomplete and all further operations fail until power is restored.
+
+use embedded_storage::nor_flash::{
+    check_erase, check_read, check_write, ErrorType, NorFlash, NorFlashError, NorFlashErrorKind,
+    ReadNorFlash,
+};
+
+/// Maximum number of pages whose erase cycles are counted
+const MAX_PAGES: usize = 64;
+
+/// Simulated flash memory errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimError {
+    NotAligned,
+    OutOfBounds,
+    /// Attempt to program a byte that is not erased
+    NotErased,
+    /// Power failure
+    PowerLoss,
+}
+
+impl NorFlashError for SimError {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            SimError::NotAligned => NorFlashErrorKind::NotAligned,
+            SimError::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl From<NorFlashErrorKind> for SimError {
+    fn from(kind: NorFlashErrorKind) -> Self {
+        match kind {
+            NorFlashErrorKind::NotAligned => SimError::NotAligned,
+            _ => SimError::OutOfBounds,
+        }
+    }
+}
+
+/// Simulated flash memory of `SIZE` bytes with erase pages of `ERASE_SIZE`
+/// bytes and a write granularity of `WRITE_SIZE` bytes
+#[derive(Clone)]
+pub struct SimFlash<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> {
+    data: [u8; SIZE],
+    erase_counts: [u32; MAX_PAGES],
+    /// Number of byte operations until a power failure
+    budget: Option<usize>,
+    powered: bool,
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize>
+    SimFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+{
+    /// Create an erased flash memory
+    pub fn new() -> Self {
+        assert!(SIZE.is_multiple_of(ERASE_SIZE) && SIZE / ERASE_SIZE <= MAX_PAGES);
+        SimFlash {
+            data: [0xff; SIZE],
+            erase_counts: [0; MAX_PAGES],
+            budget: None,
+            powered: true,
+        }
+    }
+
+    /// Contents of the flash memory
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Number of erase cycles of a page
+    pub fn erase_count(&self, page: usize) -> u32 {
+        self.erase_counts[page]
+    }
+
+    /// Fail after `operations` byte operations
+    pub fn set_power_loss_after(&mut self, operations: usize) {
+        self.budget = Some(operations);
+    }
+
+    /// Check if a power failure occurred
+    pub fn power_lost(&self) -> bool {
+        !self.powered
+    }
+
+    /// Restore power and disable power failure injection
+    pub fn restore_power(&mut self) {
+        self.budget = None;
+        self.powered = true;
+    }
+
+    /// Consume one byte operation
+    fn consume(&mut self) -> Result<(), SimError> {
+        match self.budget {
+            Some(0) => {
+                self.powered = false;
+                Err(SimError::PowerLoss)
+            }
+            Some(ref mut n) => {
+                *n -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn check_power(&self) -> Result<(), SimError> {
+        if self.powered {
+            Ok(())
+        } else {
+            Err(SimError::PowerLoss)
+        }
+    }
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> Default
+    for SimFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> ErrorType
+    for SimFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+{
+    type Error = SimError;
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> ReadNorFlash
+    for SimFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+{
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), SimError> {
+        check_read(self, offset, bytes.len())?;
+        self.check_power()?;
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        SIZE
+    }
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize> NorFlash
+    for SimFlash<SIZE, ERASE_SIZE, WRITE_SIZE>
+{
+    const WRITE_SIZE: usize = WRITE_SIZE;
+    const ERASE_SIZE: usize = ERASE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), SimError> {
+        check_erase(self, from, to)?;
+        self.check_power()?;
+        for page in (from as usize / ERASE_SIZE)..(to as usize / ERASE_SIZE) {
+            self.erase_counts[page] += 1;
+            for i in page * ERASE_SIZE..(page + 1) * ERASE_SIZE {
+                self.consume()?;
+                self.data[i] = 0xff;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), SimError> {
+        check_write(self, offset, bytes.len())?;
+        self.check_power()?;
+        let offset = offset as usize;
+        if self.data[offset..offset + bytes.len()]
+            .iter()
+            .any(|b| *b != 0xff)
+        {
+            return Err(SimError::NotErased);
+        }
+        for (i, byte) in bytes.iter().enumerate() {
+            self.consume()?;
+            self.data[offset + i] = *byte;
+        }
+        Ok(())
+    }
+}
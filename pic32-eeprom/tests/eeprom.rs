@@ -0,0 +1,201 @@
+use pic32_eeprom::sim::{SimError, SimFlash};
+use pic32_eeprom::{Eeprom, Error};
+
+/// 4 pages of 1 KiB, word programming
+type Flash = SimFlash<4096, 1024, 4>;
+
+/// 2 pages of 2 KiB, quad-word programming
+type QuadFlash = SimFlash<4096, 2048, 16>;
+
+fn read_u32<F: embedded_storage::nor_flash::NorFlash>(
+    eeprom: &mut Eeprom<F>,
+    key: u16,
+) -> Option<u32> {
+    let mut buf = [0; 4];
+    eeprom
+        .read(key, &mut buf)
+        .unwrap()
+        .map(|len| u32::from_le_bytes(buf[..len].try_into().unwrap()))
+}
+
+#[test]
+fn write_read_remove() {
+    let mut flash = Flash::new();
+    let mut eeprom = Eeprom::new(&mut flash, 0, 4).unwrap();
+    assert_eq!(read_u32(&mut eeprom, 1), None);
+    eeprom.write(1, &10u32.to_le_bytes()).unwrap();
+    eeprom.write(2, &20u32.to_le_bytes()).unwrap();
+    eeprom.write(1, &11u32.to_le_bytes()).unwrap();
+    assert_eq!(read_u32(&mut eeprom, 1), Some(11));
+    assert_eq!(read_u32(&mut eeprom, 2), Some(20));
+
+    eeprom.remove(1).unwrap();
+    assert_eq!(read_u32(&mut eeprom, 1), None);
+    assert_eq!(read_u32(&mut eeprom, 2), Some(20));
+
+    eeprom.write(3, &[]).unwrap();
+    let mut buf = [0; 1];
+    assert_eq!(eeprom.read(3, &mut buf), Ok(Some(0)));
+    assert_eq!(eeprom.read(2, &mut buf), Err(Error::BufferTooSmall));
+    assert_eq!(eeprom.write(0xffff, &[0]), Err(Error::InvalidKey));
+    assert_eq!(eeprom.write(4, &[0; 1024]), Err(Error::TooLarge));
+}
+
+#[test]
+fn invalid_config() {
+    let mut flash = Flash::new();
+    assert!(matches!(
+        Eeprom::new(&mut flash, 0, 1),
+        Err(Error::InvalidConfig)
+    ));
+    assert!(matches!(
+        Eeprom::new(&mut flash, 1024, 4),
+        Err(Error::InvalidConfig)
+    ));
+    assert!(matches!(
+        Eeprom::new(&mut flash, 512, 2),
+        Err(Error::InvalidConfig)
+    ));
+}
+
+#[test]
+fn values_persist() {
+    let mut flash = Flash::new();
+    let mut eeprom = Eeprom::new(&mut flash, 0, 4).unwrap();
+    eeprom.write(7, &70u32.to_le_bytes()).unwrap();
+    eeprom.write(8, &80u32.to_le_bytes()).unwrap();
+    eeprom.remove(8).unwrap();
+
+    let mut eeprom = Eeprom::new(&mut flash, 0, 4).unwrap();
+    assert_eq!(read_u32(&mut eeprom, 7), Some(70));
+    assert_eq!(read_u32(&mut eeprom, 8), None);
+}
+
+#[test]
+fn garbage_collection_and_wear_levelling() {
+    let mut flash = Flash::new();
+    let mut eeprom = Eeprom::new(&mut flash, 0, 4).unwrap();
+    let mut expected = [None; 8];
+    for i in 0..2000u32 {
+        let key = (i % 8) as u16;
+        if i % 13 == 0 {
+            eeprom.remove(key).unwrap();
+            expected[key as usize] = None;
+        } else {
+            eeprom.write(key, &i.to_le_bytes()).unwrap();
+            expected[key as usize] = Some(i);
+        }
+    }
+    for (key, value) in expected.iter().enumerate() {
+        assert_eq!(read_u32(&mut eeprom, key as u16), *value);
+    }
+
+    let mut eeprom = Eeprom::new(&mut flash, 0, 4).unwrap();
+    for (key, value) in expected.iter().enumerate() {
+        assert_eq!(read_u32(&mut eeprom, key as u16), *value);
+    }
+
+    let counts: Vec<u32> = (0..4).map(|page| flash.erase_count(page)).collect();
+    let max = *counts.iter().max().unwrap();
+    let min = *counts.iter().min().unwrap();
+    assert!(min > 5, "erase counts {counts:?}");
+    assert!(max - min <= 1, "erase counts {counts:?}");
+}
+
+#[test]
+fn full() {
+    let mut flash = QuadFlash::new();
+    let mut eeprom = Eeprom::new(&mut flash, 0, 2).unwrap();
+    let value = [0x55; 500];
+    let mut key = 0;
+    let result = loop {
+        match eeprom.write(key, &value) {
+            Ok(()) => key += 1,
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(result, Error::Full);
+    assert_eq!(key, 3);
+    let mut buf = [0; 500];
+    for k in 0..key {
+        assert_eq!(eeprom.read(k, &mut buf), Ok(Some(500)));
+        assert_eq!(buf, value);
+    }
+    // overwriting an existing value is still possible
+    eeprom.write(0, &[0xaa; 500]).unwrap();
+    assert_eq!(eeprom.read(0, &mut buf), Ok(Some(500)));
+    assert_eq!(buf, [0xaa; 500]);
+}
+
+/// Interrupt a sequence of writes by a power failure after every possible
+/// number of byte operations and check that each value is either the old or
+/// the new value after remounting
+fn power_loss<const SIZE: usize, const ERASE_SIZE: usize, const WRITE_SIZE: usize>(
+    pages: usize,
+    writes: u32,
+) {
+    const KEYS: u32 = 5;
+    let mut base = SimFlash::<SIZE, ERASE_SIZE, WRITE_SIZE>::new();
+    let mut eeprom = Eeprom::new(&mut base, 0, pages).unwrap();
+    for key in 0..KEYS {
+        eeprom.write(key as u16, &key.to_le_bytes()).unwrap();
+    }
+
+    let mut budget = 0;
+    loop {
+        let mut flash = base.clone();
+        let mut expected: Vec<u32> = (0..KEYS).collect();
+        flash.set_power_loss_after(budget);
+        let mut eeprom = Eeprom::new(&mut flash, 0, pages).unwrap();
+        let mut in_flight = None;
+        for i in 0..writes {
+            let key = i % KEYS;
+            let value = 100 + i;
+            match eeprom.write(key as u16, &value.to_le_bytes()) {
+                Ok(()) => expected[key as usize] = value,
+                Err(e) => {
+                    assert_eq!(e, Error::Flash(SimError::PowerLoss));
+                    in_flight = Some((key, value));
+                    break;
+                }
+            }
+        }
+        let Some((key, value)) = in_flight else {
+            // all writes completed
+            break;
+        };
+        flash.restore_power();
+
+        let mut eeprom = Eeprom::new(&mut flash, 0, pages).unwrap();
+        for k in 0..KEYS {
+            let actual = read_u32(&mut eeprom, k as u16).unwrap();
+            if k == key {
+                assert!(
+                    actual == expected[k as usize] || actual == value,
+                    "budget {budget}: key {k} value {actual}"
+                );
+            } else {
+                assert_eq!(actual, expected[k as usize], "budget {budget}: key {k}");
+            }
+        }
+        // the EEPROM must remain usable
+        for k in 0..KEYS {
+            eeprom.write(k as u16, &(1000 + k).to_le_bytes()).unwrap();
+        }
+        for k in 0..KEYS {
+            assert_eq!(read_u32(&mut eeprom, k as u16), Some(1000 + k));
+        }
+        budget += 1;
+    }
+    assert!(budget > ERASE_SIZE * 2);
+}
+
+#[test]
+fn power_loss_word_programming() {
+    power_loss::<1024, 256, 4>(4, 60);
+}
+
+#[test]
+fn power_loss_quad_word_programming() {
+    power_loss::<1024, 512, 16>(2, 40);
+}
@@ -0,0 +1,50 @@
+//! Helpers to access the EDC XML tree
+
+use roxmltree::{Document, Node};
+
+use crate::Error;
+
+/// XML namespace of the EDC elements and attributes
+const NS: &str = "http://crownking/edc";
+
+/// Check if `node` is an EDC element named `name`
+pub fn is_element(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().namespace() == Some(NS) && node.tag_name().name() == name
+}
+
+/// Get an EDC attribute of `node`
+pub fn attr<'a>(node: &Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute((NS, name))
+}
+
+/// Get an EDC attribute of `node` that must be present
+pub fn required_attr<'a>(node: &Node<'a, '_>, name: &str) -> Result<&'a str, Error> {
+    attr(node, name).ok_or_else(|| {
+        Error::Missing(format!(
+            "attribute {name} of element {}",
+            node.tag_name().name()
+        ))
+    })
+}
+
+/// Find the SFR definition having the attribute `key` equal to `value`
+pub fn find_sfr<'a, 'input>(
+    doc: &'a Document<'input>,
+    key: &str,
+    value: &str,
+) -> Option<Node<'a, 'input>> {
+    doc.descendants()
+        .find(|n| is_element(n, "SFRDef") && attr(n, key) == Some(value))
+}
+
+/// Convert an EDC name like `U1RX` to a type name like `U1rx`
+pub fn type_name(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.as_str().to_lowercase().chars())
+            .collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,86 @@
+//! Generate the GPIO and PPS tables of pic32-hal from Microchip .PIC files
+//!
+//! The .PIC files are the EDC (Essential Device Characteristics) XML files of
+//! the Microchip device packs. They are also part of the `pic32-pac`
+//! repository. This crate reads local .PIC files and generates the `port!`
+//! invocations of `gpio_tables.rs` and the `pps_tables!` invocations of
+//! `pps_tables.rs`.
+
+use std::fmt;
+
+mod edc;
+mod ports;
+mod pps;
+
+pub use ports::port_tables;
+pub use pps::pps_tables;
+
+/// First lines of the generated files
+pub const HEADER: &str = "// generated by edc2tables\n\n";
+
+/// A device variant corresponding to a device feature of pic32-hal
+pub struct Device {
+    /// Name of the pic32-hal feature
+    pub feature: &'static str,
+
+    /// Path of the .PIC file relative to the root of the `pic32-pac`
+    /// repository
+    pub edc_path: &'static str,
+}
+
+/// Device variants supported by pic32-hal
+///
+/// A new device variant requires a new entry here.
+pub const DEVICES: &[Device] = &[
+    Device {
+        feature: "pic32mx1xxfxxxb",
+        edc_path: "pic32mx2xx/pic32mx1xxfxxxb/PIC32MX170F256B.PIC",
+    },
+    Device {
+        feature: "pic32mx2xxfxxxb",
+        edc_path: "pic32mx2xx/pic32mx2xxfxxxb/PIC32MX270F256B.PIC",
+    },
+    Device {
+        feature: "pic32mx2x4fxxxb",
+        edc_path: "pic32mx2xx/pic32mx2x4fxxxb/PIC32MX274F256B.PIC",
+    },
+    Device {
+        feature: "pic32mx37x",
+        edc_path: "pic32mx470/pic32mx37xfxxxl/PIC32MX370F512L.PIC",
+    },
+    Device {
+        feature: "pic32mx47x",
+        edc_path: "pic32mx470/pic32mx47xfxxxl/PIC32MX470F512L.PIC",
+    },
+];
+
+/// Errors that can occur when generating the tables
+#[derive(Debug)]
+pub enum Error {
+    /// The .PIC file is not well-formed XML
+    Xml(roxmltree::Error),
+
+    /// A required element or attribute is missing
+    Missing(String),
+
+    /// An element or attribute has an unexpected value
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Xml(e) => write!(f, "XML error: {e}"),
+            Error::Missing(what) => write!(f, "missing {what}"),
+            Error::Invalid(what) => write!(f, "invalid {what}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<roxmltree::Error> for Error {
+    fn from(e: roxmltree::Error) -> Self {
+        Error::Xml(e)
+    }
+}
@@ -0,0 +1,90 @@
+//! edc2tables
+//!
+//! Create the PIC32 port or PPS tables from the .PIC files of a local
+//! `pic32-pac` repository, e.g.
+//!
+//! ```sh
+//! cargo run -- ports path/to/pic32-pac -o ../src/gpio_tables.rs
+//! cargo run -- pps path/to/pic32-pac -o ../src/pps_tables.rs
+//! ```
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use edc2tables::{port_tables, pps_tables, DEVICES, HEADER};
+
+const USAGE: &str = "usage: edc2tables <ports|pps> <pic32-pac directory> [-o <output file>]";
+
+enum Tables {
+    Ports,
+    Pps,
+}
+
+struct Args {
+    tables: Tables,
+    pac_dir: PathBuf,
+    output: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut tables = None;
+    let mut pac_dir = None;
+    let mut output = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                output = Some(args.next().ok_or("missing output file")?.into());
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            "ports" if tables.is_none() => tables = Some(Tables::Ports),
+            "pps" if tables.is_none() => tables = Some(Tables::Pps),
+            _ if tables.is_some() && pac_dir.is_none() => pac_dir = Some(arg.into()),
+            _ => return Err(format!("unexpected argument: {arg}\n{USAGE}")),
+        }
+    }
+    Ok(Args {
+        tables: tables.ok_or(USAGE)?,
+        pac_dir: pac_dir.ok_or(USAGE)?,
+        output,
+    })
+}
+
+fn generate(args: &Args) -> Result<String, String> {
+    let mut out = String::from(HEADER);
+    for device in DEVICES {
+        let path = args.pac_dir.join(device.edc_path);
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let doc =
+            roxmltree::Document::parse(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        let table = match args.tables {
+            Tables::Ports => port_tables(&doc, device.feature),
+            Tables::Pps => pps_tables(&doc, device.feature),
+        }
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+        out.push_str(&table);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn main() -> ExitCode {
+    let result = parse_args().and_then(|args| {
+        let out = generate(&args)?;
+        match &args.output {
+            Some(path) => fs::write(path, out).map_err(|e| format!("{}: {e}", path.display())),
+            None => io::stdout()
+                .write_all(out.as_bytes())
+                .map_err(|e| e.to_string()),
+        }
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(msg) => {
+            eprintln!("{msg}");
+            ExitCode::FAILURE
+        }
+    }
+}
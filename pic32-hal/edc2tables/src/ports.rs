@@ -0,0 +1,101 @@
+//! Generation of the `port!` invocations of `gpio_tables.rs`
+
+use std::fmt::Write;
+
+use roxmltree::Document;
+
+use crate::edc::{find_sfr, required_attr};
+use crate::Error;
+
+/// Kind of a port bit
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bit {
+    /// Not implemented
+    None,
+    /// Digital I/O pin
+    Digital,
+    /// I/O pin with analog function
+    Analog,
+}
+
+impl Bit {
+    fn code(self) -> char {
+        match self {
+            Bit::None => '-',
+            Bit::Digital => 'd',
+            Bit::Analog => 'a',
+        }
+    }
+}
+
+/// Get the bits of a port (MSB first) from the access descriptors of the
+/// PORTx and ANSELx registers
+fn port_bits(doc: &Document, letter: char) -> Result<Option<Vec<Bit>>, Error> {
+    let Some(port_reg) = find_sfr(doc, "cname", &format!("PORT{letter}")) else {
+        return Ok(None);
+    };
+    let port_access = required_attr(&port_reg, "access")?;
+    if port_access.len() != 32 {
+        return Err(Error::Invalid(format!("length of bitmask of PORT{letter}")));
+    }
+    // ports without analog functions have no ANSELx register
+    let ansel_access = match find_sfr(doc, "cname", &format!("ANSEL{letter}")) {
+        Some(ansel_reg) => required_attr(&ansel_reg, "access")?,
+        None => "--------------------------------",
+    };
+    let bits = port_access
+        .chars()
+        .zip(ansel_access.chars().chain(std::iter::repeat('-')))
+        .map(|(p, a)| match (p, a) {
+            ('n', 'n') => Bit::Analog,
+            ('n', _) => Bit::Digital,
+            _ => Bit::None,
+        })
+        .collect();
+    Ok(Some(bits))
+}
+
+/// Generate the `port!` invocations for the device feature `feature`
+pub fn port_tables(doc: &Document, feature: &str) -> Result<String, Error> {
+    let mut ports = Vec::new();
+    for letter in 'A'..='J' {
+        if let Some(bits) = port_bits(doc, letter)? {
+            ports.push((letter, bits));
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "// Port definitions for {feature}").unwrap();
+    writeln!(out, "//").unwrap();
+    for (letter, bits) in &ports {
+        let desc: String = bits.iter().map(|b| b.code()).collect();
+        writeln!(out, "// PORT{letter}: {desc}").unwrap();
+    }
+    writeln!(out, "//").unwrap();
+
+    for (letter, bits) in &ports {
+        let lc = letter.to_ascii_lowercase();
+        writeln!(out, "#[cfg(feature = \"{feature}\")]").unwrap();
+        writeln!(
+            out,
+            "port!(PORT{letter}, port{lc}, CHANGE_NOTICE_{letter}, ["
+        )
+        .unwrap();
+        for (i, bit) in bits.iter().rev().enumerate() {
+            match bit {
+                Bit::Analog => writeln!(
+                    out,
+                    "    R{letter}{i}: (r{lc}{i}, {i}, Input<Analog>, true),"
+                ),
+                Bit::Digital => {
+                    writeln!(out, "    R{letter}{i}: (r{lc}{i}, {i}, Input<Floating>),")
+                }
+                Bit::None => Ok(()),
+            }
+            .unwrap();
+        }
+        writeln!(out, "]);").unwrap();
+        writeln!(out).unwrap();
+    }
+    Ok(out)
+}
@@ -0,0 +1,173 @@
+//! Generation of the `pps_tables!` invocations of `pps_tables.rs`
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use roxmltree::{Document, Node};
+
+use crate::edc::{attr, find_sfr, is_element, required_attr, type_name};
+use crate::Error;
+
+/// Physical pin that can be mapped by means of the PPS
+struct PpsPin<'a> {
+    /// Pin name like `RA0`
+    name: String,
+    group: &'a str,
+    value: &'a str,
+    /// Name of the output mapping register like `RPA0R`, if any
+    reg: Option<String>,
+}
+
+impl PpsPin<'_> {
+    fn port_letter(&self) -> char {
+        self.name[1..2].chars().next().unwrap().to_ascii_lowercase()
+    }
+}
+
+/// Virtual input or output function
+struct Function<'a> {
+    name: &'a str,
+    group: &'a str,
+    value: &'a str,
+}
+
+fn pps_pins<'a>(doc: &'a Document, pin_list: &Node<'a, '_>) -> Result<Vec<PpsPin<'a>>, Error> {
+    let mut pins: Vec<PpsPin> = Vec::new();
+    let virtual_pins = pin_list
+        .descendants()
+        .filter(|n| is_element(n, "Pin"))
+        .flat_map(|n| n.children())
+        .filter(|n| is_element(n, "VirtualPin") && attr(n, "ppsgroup").is_some());
+    for vpin in virtual_pins {
+        let rp_name = required_attr(&vpin, "name")?;
+        // RPA0 -> RA0
+        let name = format!("R{}", rp_name.get(2..).unwrap_or_default());
+        if name.len() < 3 || pins.iter().any(|p| p.name == name) {
+            continue;
+        }
+        let reg_name = format!("{rp_name}R");
+        let reg = find_sfr(doc, "name", &reg_name).map(|_| reg_name);
+        pins.push(PpsPin {
+            name,
+            group: required_attr(&vpin, "ppsgroup")?,
+            value: required_attr(&vpin, "ppsval")?,
+            reg,
+        });
+    }
+    // sort order of the original generator (string comparison)
+    pins.sort_by(|a, b| (a.group, a.value).cmp(&(b.group, b.value)));
+    Ok(pins)
+}
+
+/// Get the virtual input and output functions
+#[allow(clippy::type_complexity)]
+fn functions<'a>(pin_list: &Node<'a, '_>) -> Result<(Vec<Function<'a>>, Vec<Function<'a>>), Error> {
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    for rpin in pin_list
+        .children()
+        .filter(|n| is_element(n, "RemappablePin"))
+    {
+        let direction = required_attr(&rpin, "direction")?;
+        let vpin = rpin
+            .children()
+            .find(|n| is_element(n, "VirtualPin"))
+            .ok_or_else(|| Error::Missing("VirtualPin of RemappablePin".into()))?;
+        let function = Function {
+            name: required_attr(&vpin, "name")?,
+            group: required_attr(&vpin, "ppsgroup")?,
+            value: attr(&vpin, "ppsval").unwrap_or_default(),
+        };
+        match direction {
+            "in" => inputs.push(function),
+            "out" => outputs.push(function),
+            _ => {}
+        }
+    }
+    inputs.sort_by_key(|f| f.group);
+    outputs.sort_by_key(|f| f.value);
+    Ok((inputs, outputs))
+}
+
+/// Generate the `pps_tables!` invocation for the device feature `feature`
+pub fn pps_tables(doc: &Document, feature: &str) -> Result<String, Error> {
+    let pin_list = doc
+        .descendants()
+        .find(|n| is_element(n, "PinList"))
+        .ok_or_else(|| Error::Missing("PinList".into()))?;
+    let pins = pps_pins(doc, &pin_list)?;
+    let (inputs, outputs) = functions(&pin_list)?;
+
+    let mut out = String::new();
+    writeln!(out, "#[cfg(feature = \"{feature}\")]").unwrap();
+    writeln!(out, "pps_tables! {{").unwrap();
+    writeln!(out, "    {{ // virtual input declarations").unwrap();
+    for input in &inputs {
+        writeln!(
+            out,
+            "        {}, {};   // PPS group #{}",
+            type_name(input.name),
+            input.name.to_lowercase(),
+            input.group
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "    }}{{ // virtual output declarations").unwrap();
+    let output_names: BTreeSet<&str> = outputs.iter().map(|f| f.name).collect();
+    for name in output_names {
+        writeln!(out, "        {}, {};", type_name(name), name.to_lowercase()).unwrap();
+    }
+
+    writeln!(out, "    }}{{ // virtual input mapping table").unwrap();
+    for input in &inputs {
+        writeln!(
+            out,
+            "        {}, {}r {{ // PPS group #{}",
+            type_name(input.name),
+            input.name.to_lowercase(),
+            input.group
+        )
+        .unwrap();
+        for pin in pins.iter().filter(|p| p.group == input.group) {
+            writeln!(
+                out,
+                "            gpio::port{}::{}<MODE>, {};",
+                pin.port_letter(),
+                pin.name,
+                pin.value
+            )
+            .unwrap();
+        }
+        writeln!(out, "        }}").unwrap();
+    }
+
+    writeln!(out, "    }}{{ // virtual output mapping table").unwrap();
+    for pin in &pins {
+        let Some(reg) = &pin.reg else {
+            continue;
+        };
+        writeln!(
+            out,
+            "        gpio::port{}::{}<MODE>, {} {{    // PPS group #{}",
+            pin.port_letter(),
+            pin.name,
+            reg.to_lowercase(),
+            pin.group
+        )
+        .unwrap();
+        for output in outputs.iter().filter(|f| f.group == pin.group) {
+            writeln!(
+                out,
+                "            {}, {};",
+                type_name(output.name),
+                output.value
+            )
+            .unwrap();
+        }
+        writeln!(out, "        }}").unwrap();
+    }
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
@@ -0,0 +1,64 @@
+use edc2tables::{port_tables, pps_tables, Error};
+use roxmltree::Document;
+
+/// Reduced .PIC files of one device of each device family
+const SAMPLES: &[(&str, &str)] = &[
+    ("pic32mx1xxfxxxb", include_str!("data/PIC32MX170F256B.PIC")),
+    ("pic32mx2xxfxxxb", include_str!("data/PIC32MX270F256B.PIC")),
+    ("pic32mx2x4fxxxb", include_str!("data/PIC32MX274F256B.PIC")),
+    ("pic32mx37x", include_str!("data/PIC32MX370F512L.PIC")),
+    ("pic32mx47x", include_str!("data/PIC32MX470F512L.PIC")),
+];
+
+/// Get the section of `tables` starting with `marker` followed by the
+/// feature name and ending before the next section
+fn section(tables: &str, marker: &str, feature: &str) -> String {
+    let start = tables.find(&format!("{marker}{feature}")).unwrap();
+    let len = tables[start + 1..]
+        .find(marker)
+        .map_or(tables.len() - start, |len| len + 1);
+    tables[start..start + len].trim_end().to_owned()
+}
+
+#[test]
+fn ports_match_gpio_tables() {
+    // the samples contain the complete port definitions of the device
+    // families and must reproduce the checked-in tables
+    let gpio_tables = include_str!("../../src/gpio_tables.rs");
+    for (feature, sample) in SAMPLES {
+        let doc = Document::parse(sample).unwrap();
+        let tables = port_tables(&doc, feature).unwrap();
+        let expected = section(gpio_tables, "// Port definitions for ", feature);
+        assert_eq!(tables, expected + "\n\n", "{feature}");
+    }
+}
+
+#[test]
+fn pps_match_pps_tables() {
+    // the samples contain the complete PPS definitions of the device families
+    // and must reproduce the checked-in tables
+    let pps_tables_rs = include_str!("../../src/pps_tables.rs");
+    for (feature, sample) in SAMPLES {
+        let doc = Document::parse(sample).unwrap();
+        let tables = pps_tables(&doc, feature).unwrap();
+        let expected = section(pps_tables_rs, "#[cfg(feature = \"", feature);
+        assert_eq!(tables, expected + "\n", "{feature}");
+    }
+}
+
+#[test]
+fn missing_pin_list() {
+    let doc = Document::parse(r#"<edc:PIC xmlns:edc="http://crownking/edc"/>"#).unwrap();
+    assert!(matches!(pps_tables(&doc, "x"), Err(Error::Missing(_))));
+}
+
+#[test]
+fn invalid_port_access() {
+    let doc = Document::parse(
+        r#"<edc:PIC xmlns:edc="http://crownking/edc">
+             <edc:SFRDef edc:cname="PORTA" edc:access="nnnn"/>
+           </edc:PIC>"#,
+    )
+    .unwrap();
+    assert!(matches!(port_tables(&doc, "x"), Err(Error::Invalid(_))));
+}
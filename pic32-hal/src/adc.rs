@@ -1,5 +1,6 @@
 //! 10-bit Analog-to-Digital Converter (ADC)
 
+use crate::int::{Interrupt, InterruptSource, Irq, PeripheralInterrupt};
 use crate::pac::ADC;
 use core::marker::PhantomData;
 
@@ -178,6 +179,16 @@ pub struct Adc<F> {
     _format: PhantomData<F>,
 }
 
+impl<F> PeripheralInterrupt for Adc<F> {
+    type Event = Irq;
+
+    const VECTOR: Interrupt = Interrupt::ADC;
+
+    fn interrupt_source(_event: Irq) -> InterruptSource {
+        InterruptSource::ADC
+    }
+}
+
 impl Adc<Unsigned32> {
     /// Create an Adc instance (unsigned 32-bit data format).
     /// `fractional == true` selects a fractional number format.
@@ -1,8 +1,14 @@
 //! Clock helper and control functions
-// Work in progress, can calculate pb_clock only, no real control functions
-// implemented
-//
 
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+use crate::devcfg;
+use crate::int::{Int, Interrupt, InterruptSource, Ipl, Isl};
+use crate::syskey;
 use crate::time::Hertz;
 use crate::time::U32Ext;
 use core::marker::PhantomData;
@@ -16,6 +22,8 @@ use core::marker::PhantomData;
 ))]
 pub mod refclock;
 
+pub mod frc_tuner;
+
 #[cfg(feature = "pic32mx2x4fxxxb")]
 use crate::pac::CRU;
 
@@ -48,6 +56,30 @@ pub struct Simple;
 
 pub struct WithRefclock;
 
+/// Frequency of the internal fast RC oscillator (FRC)
+const FRC_FREQ: u32 = 8_000_000;
+
+/// Frequency of the secondary oscillator (SOSC)
+const SOSC_FREQ: u32 = 32_768;
+
+/// Frequency of the internal low power RC oscillator (LPRC)
+const LPRC_FREQ: u32 = 31_250;
+
+/// PLL input divider values as selected by FPLLIDIV
+const PLLIDIV_VALUES: [u32; 8] = [1, 2, 3, 4, 5, 6, 10, 12];
+
+/// PLL multiplier values as selected by PLLMULT or FPLLMUL
+const PLLMULT_VALUES: [u32; 8] = [15, 16, 17, 18, 19, 20, 21, 24];
+
+/// Divider values as selected by PLLODIV, FPLLODIV or FRCDIV
+const POSTDIV_VALUES: [u32; 8] = [1, 2, 4, 8, 16, 32, 64, 256];
+
+/// Calculate the output frequency of the system PLL
+fn pll_freq(input: u32, idiv: u8, mult: u8, odiv: u8) -> u32 {
+    input / PLLIDIV_VALUES[idiv as usize & 0x07] * PLLMULT_VALUES[mult as usize & 0x07]
+        / POSTDIV_VALUES[odiv as usize & 0x07]
+}
+
 /// Clock module errors
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Error {
@@ -58,6 +90,88 @@ pub enum Error {
     InvalidArgument,
 }
 
+/// Oscillator that can be selected as clock source
+#[cfg(feature = "pic32mx2x4fxxxb")]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum ClockSource {
+    /// Internal fast RC oscillator (FRC)
+    Frc = 0b000,
+    /// System PLL (SPLL)
+    Spll = 0b001,
+    /// Primary oscillator (POSC)
+    Posc = 0b010,
+    /// Secondary oscillator (SOSC)
+    Sosc = 0b100,
+    /// Internal low power RC oscillator (LPRC)
+    Lprc = 0b101,
+    /// Internal fast RC oscillator divided by FRCDIV
+    FrcDiv = 0b111,
+}
+
+#[cfg(feature = "pic32mx2x4fxxxb")]
+impl ClockSource {
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            0b001 => Self::Spll,
+            0b010 => Self::Posc,
+            0b100 => Self::Sosc,
+            0b101 => Self::Lprc,
+            0b111 => Self::FrcDiv,
+            _ => Self::Frc,
+        }
+    }
+}
+
+/// Oscillator that can be selected as clock source
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum ClockSource {
+    /// Internal fast RC oscillator (FRC)
+    Frc = 0b000,
+    /// Internal fast RC oscillator with PLL
+    FrcPll = 0b001,
+    /// Primary oscillator (POSC)
+    Posc = 0b010,
+    /// Primary oscillator with PLL
+    PoscPll = 0b011,
+    /// Secondary oscillator (SOSC)
+    Sosc = 0b100,
+    /// Internal low power RC oscillator (LPRC)
+    Lprc = 0b101,
+    /// Internal fast RC oscillator divided by 16
+    FrcDiv16 = 0b110,
+    /// Internal fast RC oscillator divided by FRCDIV
+    FrcDiv = 0b111,
+}
+
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+impl ClockSource {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0x07 {
+            0b000 => Self::Frc,
+            0b001 => Self::FrcPll,
+            0b010 => Self::Posc,
+            0b011 => Self::PoscPll,
+            0b100 => Self::Sosc,
+            0b101 => Self::Lprc,
+            0b110 => Self::FrcDiv16,
+            _ => Self::FrcDiv,
+        }
+    }
+}
+
 #[cfg(feature = "pic32mx2x4fxxxb")]
 impl Osc {
     /// Create a new `Osc` from a possibly constant sysclock value. The sysclock
@@ -80,6 +194,55 @@ impl Osc {
         )
     }
 
+    /// Create a new `Osc` and determine the sysclock from the current
+    /// oscillator selection (OSCCON) and the system PLL settings (SPLLCON). `posc` is the frequency of the primary oscillator (e.g. the
+    /// crystal frequency). It is ignored if the primary oscillator is neither
+    /// the clock source nor the PLL input.
+    pub fn from_hardware(cru: CRU, posc: Hertz) -> Osc {
+        let sysclock = Self::sysclock_from_hardware(&cru, posc);
+        Osc { cru, sysclock }
+    }
+
+    /// Create a new `Osc` and `Refclock` and determine the sysclock from the
+    /// hardware settings. See [`Osc::from_hardware`].
+    pub fn from_hardware_with_refclock(cru: CRU, posc: Hertz) -> (Osc, refclock::Refclock) {
+        (
+            Self::from_hardware(cru, posc),
+            refclock::Refclock {
+                _private: PhantomData,
+            },
+        )
+    }
+
+    fn sysclock_from_hardware(cru: &CRU, posc: Hertz) -> Hertz {
+        let osccon = cru.osccon.read();
+        let frcdiv = osccon.frcdiv().bits();
+        let freq = match osccon.cosc().bits() {
+            0b001 => {
+                // SPLLCON is loaded from DEVCFG2 at reset but may have been
+                // changed at run time
+                let spllcon = cru.spllcon.read();
+                let input = if spllcon.plliclk().bit() {
+                    FRC_FREQ
+                } else {
+                    posc.0
+                };
+                pll_freq(
+                    input,
+                    spllcon.pllidiv().bits(),
+                    spllcon.pllmult().bits(),
+                    spllcon.pllodiv().bits(),
+                )
+            }
+            0b010 => posc.0,
+            0b100 => SOSC_FREQ,
+            0b101 => LPRC_FREQ,
+            0b111 => FRC_FREQ / POSTDIV_VALUES[frcdiv as usize & 0x07],
+            _ => FRC_FREQ,
+        };
+        freq.hz()
+    }
+
     /// Get the sysclock
     pub fn sysclock(&self) -> Hertz {
         self.sysclock
@@ -92,6 +255,16 @@ impl Osc {
         let freq = self.sysclock.0 / (div as u32 + 1);
         freq.hz()
     }
+
+    /// Determine the sysclock again from the hardware settings, e.g. after
+    /// the Fail-Safe Clock Monitor has switched to the FRC.
+    pub fn refresh_sysclock(&mut self, posc: Hertz) {
+        self.sysclock = Self::sysclock_from_hardware(&self.cru, posc);
+    }
+
+    fn regs(&self) -> &crate::pac::cru::RegisterBlock {
+        &self.cru
+    }
 }
 
 #[cfg(any(
@@ -121,6 +294,62 @@ impl Osc {
         )
     }
 
+    /// Create a new `Osc` and determine the sysclock from the current
+    /// oscillator selection and PLL settings (OSCCON) and the PLL input divider
+    /// setting of the configuration words. `posc` is the frequency of the
+    /// primary oscillator (e.g. the crystal frequency). It is ignored if the
+    /// primary oscillator is not used.
+    pub fn from_hardware(osc: OSC, posc: Hertz) -> Osc {
+        let sysclock = Self::sysclock_from_hardware(&osc, posc);
+        Osc { osc, sysclock }
+    }
+
+    /// Create a new `Osc` and `Refclock` and determine the sysclock from the
+    /// hardware settings. See [`Osc::from_hardware`].
+    pub fn from_hardware_with_refclock(osc: OSC, posc: Hertz) -> (Osc, refclock::Refclock) {
+        (
+            Self::from_hardware(osc, posc),
+            refclock::Refclock {
+                _private: PhantomData,
+            },
+        )
+    }
+
+    fn sysclock_from_hardware(osc: &OSC, posc: Hertz) -> Hertz {
+        let osccon = osc.osccon.read();
+        let pll = |input: u32| {
+            // the PLL input divider can be configured by FPLLIDIV only
+            let idiv = devcfg::devcfg2() as u8 & 0x07;
+            pll_freq(
+                input,
+                idiv,
+                osccon.pllmult().bits(),
+                osccon.pllodiv().bits(),
+            )
+        };
+        let freq = match osccon.cosc().bits() {
+            0b000 => FRC_FREQ,
+            0b001 => pll(FRC_FREQ),
+            0b010 => posc.0,
+            0b011 => pll(posc.0),
+            0b100 => SOSC_FREQ,
+            0b101 => LPRC_FREQ,
+            0b110 => FRC_FREQ / 16,
+            _ => FRC_FREQ / POSTDIV_VALUES[osccon.frcdiv().bits() as usize & 0x07],
+        };
+        freq.hz()
+    }
+
+    /// Determine the sysclock again from the hardware settings, e.g. after
+    /// the Fail-Safe Clock Monitor has switched to the FRC.
+    pub fn refresh_sysclock(&mut self, posc: Hertz) {
+        self.sysclock = Self::sysclock_from_hardware(&self.osc, posc);
+    }
+
+    fn regs(&self) -> &crate::pac::osc::RegisterBlock {
+        &self.osc
+    }
+
     /// Get the sysclock
     pub fn sysclock(&self) -> Hertz {
         self.sysclock
@@ -134,3 +363,116 @@ impl Osc {
         freq.hz()
     }
 }
+
+/// FRC oscillator tuning
+impl Osc {
+    /// Get the FRC tuning value (OSCTUN)
+    ///
+    /// A value of 0 corresponds to the center frequency. Positive values
+    /// increase and negative values decrease the FRC frequency.
+    pub fn frc_tuning(&self) -> i8 {
+        // sign extend the 6-bit value
+        ((self.regs().osctun.read().tun().bits() << 2) as i8) >> 2
+    }
+
+    /// Set the FRC tuning value (OSCTUN)
+    ///
+    /// `tun` must be in the range -32..=31. Returns an `InvalidArgument` error
+    /// otherwise.
+    pub fn set_frc_tuning(&mut self, tun: i8) -> Result<(), Error> {
+        if !(-32..=31).contains(&tun) {
+            return Err(Error::InvalidArgument);
+        }
+        self.regs()
+            .osctun
+            .write(|w| unsafe { w.tun().bits(tun as u8 & 0x3f) });
+        Ok(())
+    }
+}
+
+/// Clock switching and Fail-Safe Clock Monitor (FSCM)
+///
+/// Clock switching requires clock switching to be enabled by the FCKSM
+/// configuration setting. The FSCM, if enabled by FCKSM as well, switches to
+/// the FRC when the clock fails and sets the clock fail flag. An interrupt can
+/// be generated in this case. The value returned by `sysclock()` is not
+/// updated automatically when the clock source changes; call
+/// `refresh_sysclock()` to do so.
+impl Osc {
+    /// Get the currently active clock source
+    pub fn clock_source(&self) -> ClockSource {
+        ClockSource::from_bits(self.regs().osccon.read().cosc().bits())
+    }
+
+    /// Initiate a clock switch to the clock source `source`
+    ///
+    /// Returns an `InvalidState` error if another clock switch is ongoing. The
+    /// clock switch is completed when `clock_switch_ongoing()` returns false.
+    /// If the new clock source does not start, the clock switch does not
+    /// complete and the current clock source is kept.
+    pub fn switch_clock(&mut self, source: ClockSource) -> Result<(), Error> {
+        if self.clock_switch_ongoing() {
+            return Err(Error::InvalidState);
+        }
+        let regs = self.regs();
+        critical_section::with(|_| {
+            unsafe {
+                syskey::unlock();
+                regs.osccon.modify(|_, w| w.nosc().bits(source as u8));
+            }
+            regs.oscconset.write(|w| w.oswen().bit(true));
+            syskey::lock();
+        });
+        Ok(())
+    }
+
+    /// Check if a clock switch is ongoing
+    pub fn clock_switch_ongoing(&self) -> bool {
+        self.regs().osccon.read().oswen().bit()
+    }
+
+    /// Check if the Fail-Safe Clock Monitor has detected a clock failure
+    pub fn clock_failed(&self) -> bool {
+        self.regs().osccon.read().cf().bit()
+    }
+
+    /// Clear the clock fail flag
+    pub fn clear_clock_failure(&mut self) {
+        let regs = self.regs();
+        critical_section::with(|_| {
+            unsafe { syskey::unlock() };
+            regs.oscconclr.write(|w| w.cf().bit(true));
+            syskey::lock();
+        });
+    }
+
+    /// Try to restart the failed oscillator
+    ///
+    /// Clears the clock fail flag and initiates a clock switch to `source`,
+    /// typically the primary oscillator with or without PLL. Check with
+    /// `clock_switch_ongoing()` and `clock_source()` whether the restart was
+    /// successful.
+    pub fn restart_clock(&mut self, source: ClockSource) -> Result<(), Error> {
+        self.clear_clock_failure();
+        self.switch_clock(source)
+    }
+
+    /// Enable the clock failure interrupt and set its priority levels
+    pub fn enable_clock_failure_interrupt(&self, int: &Int, ipl: Ipl, isl: Isl) {
+        int.set_ipl(Interrupt::FAIL_SAFE_MONITOR, ipl);
+        int.set_isl(Interrupt::FAIL_SAFE_MONITOR, isl);
+        int.ei(InterruptSource::FAIL_SAFE_MONITOR);
+    }
+
+    /// Disable the clock failure interrupt
+    pub fn disable_clock_failure_interrupt(&self, int: &Int) {
+        int.di(InterruptSource::FAIL_SAFE_MONITOR);
+    }
+
+    /// Clear the clock failure interrupt flag
+    ///
+    /// To be called by the ISR after having cleared the clock fail flag.
+    pub fn clear_clock_failure_interrupt(&self, int: &Int) {
+        int.clear_if(InterruptSource::FAIL_SAFE_MONITOR);
+    }
+}
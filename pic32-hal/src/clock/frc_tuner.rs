@@ -0,0 +1,86 @@
+//! Closed-loop FRC tuning based on USB start-of-frame (SOF) events
+//!
+//! A USB host generates a SOF packet every millisecond with a high accuracy.
+//! The `FrcTuner` measures the time between SOF events with the MIPS core
+//! timer, which is clocked by the FRC when the FRC (with PLL) is the system
+//! clock source, and adjusts the FRC tuning register (OSCTUN) until the
+//! measured frame period matches 1 ms. This makes crystal-less USB operation
+//! possible.
+//!
+//! Typical use: enable the SOF interrupt with `UsbBus::enable_sof_irq()` and
+//! call `FrcTuner::update()` with the current USB frame number from the USB
+//! ISR.
+
+use super::Osc;
+use crate::time::Hertz;
+use mips_mcu::core_timer::read_count;
+
+/// USB frame numbers are 11 bit wide
+const FRAME_NUMBER_MASK: u16 = 0x7ff;
+
+/// Closed-loop FRC tuner
+pub struct FrcTuner {
+    ticks_per_frame: u32,
+    window: u16,
+    tolerance_ppm: u32,
+    start: Option<(u16, u32)>,
+}
+
+impl FrcTuner {
+    /// Create a new `FrcTuner`
+    ///
+    /// `sysclock` is the nominal system clock frequency. The FRC frequency is
+    /// measured over `window` USB frames and adjusted by one tuning step if
+    /// the deviation exceeds `tolerance_ppm`. `window` must be greater than 0
+    /// and less than or equal to 1024; otherwise, this function will panic.
+    pub const fn new(sysclock: Hertz, window: u16, tolerance_ppm: u32) -> Self {
+        assert!(window > 0 && window <= 1024);
+        FrcTuner {
+            // the core timer is incremented every other sysclock cycle
+            ticks_per_frame: sysclock.0 / 2 / 1000,
+            window,
+            tolerance_ppm,
+            start: None,
+        }
+    }
+
+    /// Restart the measurement, e.g. after a USB suspend or reset
+    pub fn reset(&mut self) {
+        self.start = None;
+    }
+
+    /// Process a SOF event
+    ///
+    /// To be called shortly after a SOF event with the current USB frame
+    /// number. Missed SOF events are tolerated. When a measurement window is
+    /// completed, the FRC tuning is adjusted, if needed, and the measured
+    /// frequency deviation in ppm is returned. A positive value means that
+    /// the FRC was too fast.
+    pub fn update(&mut self, osc: &mut Osc, frame_number: u16) -> Option<i32> {
+        let count = read_count();
+        let frame_number = frame_number & FRAME_NUMBER_MASK;
+        let (start_frame, start_count) = match self.start {
+            Some(start) => start,
+            None => {
+                self.start = Some((frame_number, count));
+                return None;
+            }
+        };
+        let frames = frame_number.wrapping_sub(start_frame) & FRAME_NUMBER_MASK;
+        if frames < self.window {
+            return None;
+        }
+        self.start = Some((frame_number, count));
+
+        let ticks = count.wrapping_sub(start_count) as i64;
+        let expected = frames as i64 * self.ticks_per_frame as i64;
+        let deviation = ((ticks - expected) * 1_000_000 / expected) as i32;
+        if deviation.unsigned_abs() > self.tolerance_ppm {
+            let tun = osc.frc_tuning();
+            let new_tun = if deviation > 0 { tun - 1 } else { tun + 1 };
+            // ignore the error when the end of the tuning range is reached
+            let _ = osc.set_frc_tuning(new_tun);
+        }
+        Some(deviation)
+    }
+}
@@ -0,0 +1,208 @@
+//! Deep Sleep mode of the PIC32MX XLP devices
+//!
+//! In Deep Sleep mode, the core supply is turned off and only the deep sleep
+//! logic, the RTCC and the deep sleep watchdog timer (DSWDT) remain powered.
+//! Wake-up is signaled by a device reset. `DeepSleep::woke_from_deep_sleep()`
+//! tells whether the reset is caused by a wake-up from Deep Sleep and
+//! `DeepSleep::wake_sources()` tells what caused the wake-up.
+//!
+//! The deep sleep general purpose registers (DSGPR) keep their values in Deep
+//! Sleep mode and can be used to persist a few words. DSGPR0 is always
+//! retained; the other registers are retained only if enabled with
+//! `DeepSleep::set_gpr_retention()`.
+//!
+//! The DSWDT is configured by the configuration words (DSWDTEN, DSWDTPS and
+//! DSWDTOSC). Writes to the DSCON register must be carried out twice in a row
+//! to take effect. This is handled by this module.
+
+use crate::devcfg;
+use crate::pac::{CRU, DSCTRL};
+use crate::syskey;
+use enumflags2::{bitflags, BitFlags};
+use mips_mcu::interrupt;
+
+// bit masks for DSCON register
+const DSCON_DSEN_MASK: u32 = 0x8000;
+const DSCON_DSGPREN_MASK: u32 = 0x2000;
+const DSCON_RTCCWDIS_MASK: u32 = 0x0100;
+const DSCON_DSBOR_MASK: u32 = 0x0002;
+const DSCON_RELEASE_MASK: u32 = 0x0001;
+
+// bit masks of DEVCFG2 related to Deep Sleep
+const DEVCFG2_DSWDTPS_MASK: u32 = 0x1f00_0000;
+const DEVCFG2_DSWDTPS_POSITION: usize = 24;
+const DEVCFG2_DSWDTOSC_MASK: u32 = 0x2000_0000;
+const DEVCFG2_DSWDTEN_MASK: u32 = 0x4000_0000;
+
+/// Number of deep sleep general purpose registers (DSGPR0 ... DSGPR32)
+pub const N_GPR: usize = 33;
+
+// check the register layout that `DeepSleep::gpr_ptr()` relies on
+const _: () = {
+    use crate::pac::dsctrl::RegisterBlock;
+    use core::mem::offset_of;
+    assert!(offset_of!(RegisterBlock, dsgpr0) == 0x20);
+    assert!(offset_of!(RegisterBlock, dsgpr1) == 0x40);
+    assert!(offset_of!(RegisterBlock, dsgpr2) == offset_of!(RegisterBlock, dsgpr1) + 4);
+    assert!(offset_of!(RegisterBlock, dsgpr16) == offset_of!(RegisterBlock, dsgpr1) + 15 * 4);
+    assert!(offset_of!(RegisterBlock, dsgpr32) == offset_of!(RegisterBlock, dsgpr1) + 31 * 4);
+    assert!(core::mem::size_of::<RegisterBlock>() == offset_of!(RegisterBlock, dsgpr32) + 4);
+};
+
+/// Deep Sleep wake-up sources
+#[bitflags]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u16)]
+pub enum WakeSource {
+    /// External interrupt INT0
+    Int0 = 0x0100,
+    /// Deep Sleep fault detected (contents of the DSGPR may be corrupted)
+    Fault = 0x0080,
+    /// Deep Sleep watchdog timer timeout
+    Watchdog = 0x0010,
+    /// RTCC alarm
+    Rtcc = 0x0008,
+    /// MCLR pin asserted
+    Mclr = 0x0004,
+}
+
+/// Deep Sleep control
+pub struct DeepSleep {
+    dsctrl: DSCTRL,
+}
+
+impl DeepSleep {
+    /// Create a new `DeepSleep` instance
+    pub fn new(dsctrl: DSCTRL) -> Self {
+        DeepSleep { dsctrl }
+    }
+
+    /// Return the DSCTRL peripheral consuming the `DeepSleep` instance
+    pub fn free(self) -> DSCTRL {
+        self.dsctrl
+    }
+
+    /// Write to DSCON twice as required by the hardware
+    fn modify_dscon(&mut self, set: u32, clear: u32) {
+        critical_section::with(|_| {
+            unsafe { syskey::unlock() };
+            let dscon = self.dsctrl.dscon.read().bits() & !clear | set;
+            self.dsctrl.dscon.write(|w| unsafe { w.bits(dscon) });
+            self.dsctrl.dscon.write(|w| unsafe { w.bits(dscon) });
+            syskey::lock();
+        });
+    }
+
+    /// Check if the last reset was a wake-up from Deep Sleep mode
+    pub fn woke_from_deep_sleep(&self) -> bool {
+        unsafe { (*CRU::ptr()).rcon.read().dpslp().bit() }
+    }
+
+    /// Get the sources that caused the wake-up from Deep Sleep mode
+    pub fn wake_sources(&self) -> BitFlags<WakeSource> {
+        BitFlags::from_bits_truncate(self.dsctrl.dswake.read().bits() as u16)
+    }
+
+    /// Finish the wake-up from Deep Sleep mode
+    ///
+    /// To be called during startup after a wake-up and after the I/O ports
+    /// have been configured. Clears the wake-up flags and the RELEASE bit so
+    /// that the I/O pins are no longer held in their Deep Sleep states.
+    pub fn release(&mut self) {
+        self.dsctrl.dswake.write(|w| unsafe { w.bits(0) });
+        self.modify_dscon(0, DSCON_RELEASE_MASK);
+        unsafe { (*CRU::ptr()).rconclr.write(|w| w.dpslp().bit(true)) };
+    }
+
+    /// Enable or disable the retention of DSGPR1 ... DSGPR32 in Deep Sleep
+    /// mode. DSGPR0 is always retained.
+    pub fn set_gpr_retention(&mut self, enable: bool) {
+        if enable {
+            self.modify_dscon(DSCON_DSGPREN_MASK, 0);
+        } else {
+            self.modify_dscon(0, DSCON_DSGPREN_MASK);
+        }
+    }
+
+    /// Enable or disable the wake-up by an RTCC alarm
+    pub fn set_rtcc_wakeup(&mut self, enable: bool) {
+        if enable {
+            self.modify_dscon(0, DSCON_RTCCWDIS_MASK);
+        } else {
+            self.modify_dscon(DSCON_RTCCWDIS_MASK, 0);
+        }
+    }
+
+    /// Enable or disable the brown-out reset in Deep Sleep mode
+    pub fn set_brown_out_reset(&mut self, enable: bool) {
+        if enable {
+            self.modify_dscon(DSCON_DSBOR_MASK, 0);
+        } else {
+            self.modify_dscon(0, DSCON_DSBOR_MASK);
+        }
+    }
+
+    fn gpr_ptr(&self, ndx: usize) -> *mut u32 {
+        assert!(ndx < N_GPR);
+        if ndx == 0 {
+            return self.dsctrl.dsgpr0.as_ptr();
+        }
+        // DSGPR1 ... DSGPR32 are contiguous words separate from DSGPR0
+        let base = self.dsctrl.dsgpr1.as_ptr() as usize;
+        (base + (ndx - 1) * 4) as *mut u32
+    }
+
+    /// Read a deep sleep general purpose register (DSGPR)
+    ///
+    /// Panics if `ndx` is not less than `N_GPR`.
+    pub fn gpr(&self, ndx: usize) -> u32 {
+        unsafe { core::ptr::read_volatile(self.gpr_ptr(ndx)) }
+    }
+
+    /// Write to a deep sleep general purpose register (DSGPR)
+    ///
+    /// Panics if `ndx` is not less than `N_GPR`.
+    pub fn set_gpr(&mut self, ndx: usize, value: u32) {
+        unsafe { core::ptr::write_volatile(self.gpr_ptr(ndx), value) };
+    }
+
+    /// Check if the deep sleep watchdog timer is enabled by the configuration
+    /// words
+    pub fn watchdog_enabled(&self) -> bool {
+        devcfg::devcfg2() & DEVCFG2_DSWDTEN_MASK != 0
+    }
+
+    /// Get the timeout of the deep sleep watchdog timer in milliseconds as
+    /// configured by the configuration words
+    ///
+    /// Returns `None` if the deep sleep watchdog timer is disabled.
+    pub fn watchdog_timeout_ms(&self) -> Option<u64> {
+        let cfg2 = devcfg::devcfg2();
+        if cfg2 & DEVCFG2_DSWDTEN_MASK == 0 {
+            return None;
+        }
+        let ps = (cfg2 & DEVCFG2_DSWDTPS_MASK) >> DEVCFG2_DSWDTPS_POSITION;
+        // clocked either by the LPRC or by the SOSC
+        let freq = if cfg2 & DEVCFG2_DSWDTOSC_MASK != 0 {
+            32_000
+        } else {
+            32_768
+        };
+        Some((1u64 << (ps + 5)) * 1000 / freq)
+    }
+
+    /// Enter Deep Sleep mode
+    ///
+    /// Does not return. The device is reset when waking up.
+    pub fn enter(&mut self) -> ! {
+        interrupt::disable();
+        let cru = unsafe { &*CRU::ptr() };
+        unsafe { syskey::unlock() };
+        cru.oscconset.write(|w| w.slpen().bit(true));
+        syskey::lock();
+        self.modify_dscon(DSCON_DSEN_MASK, 0);
+        loop {
+            interrupt::wait();
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Read access to the device configuration words (DEVCFG0 ... DEVCFG3)
+//!
+//! The configuration words are located at the end of the boot flash memory
+//! and are read via KSEG1 (uncached).
+
+use core::ptr::read_volatile;
+
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx2x4fxxxb",
+))]
+const DEVCFG3_ADDR: usize = 0xbfc0_0bf0;
+
+#[cfg(any(feature = "pic32mx37x", feature = "pic32mx47x"))]
+const DEVCFG3_ADDR: usize = 0xbfc0_2ff0;
+
+fn read_devcfg(ndx: usize) -> u32 {
+    // DEVCFG3 is located at the lowest address followed by DEVCFG2 etc.
+    let addr = DEVCFG3_ADDR + 4 * (3 - ndx);
+    unsafe { read_volatile(addr as *const u32) }
+}
+
+/// Read configuration word DEVCFG1
+pub fn devcfg1() -> u32 {
+    read_devcfg(1)
+}
+
+/// Read configuration word DEVCFG2
+pub fn devcfg2() -> u32 {
+    read_devcfg(2)
+}
+
+/// Read configuration word DEVCFG3
+pub fn devcfg3() -> u32 {
+    read_devcfg(3)
+}
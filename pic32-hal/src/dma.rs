@@ -1,10 +1,28 @@
 //! DMA Controller
+//!
+//! Besides the register-level operations, the `Ops` trait provides blocking
+//! memory-to-memory operations (copy and fill) and the calculation of CRCs by
+//! means of the CRC generator of the DMA controller. These operations
+//! reconfigure the channel and use a single forced cell transfer per block.
 
-use crate::int::InterruptSource;
+use core::cmp::max;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::int::{Interrupt, InterruptSource, Irq, PeripheralInterrupt};
 use crate::pac::{DMAC, DMAC0, DMAC1, DMAC2, DMAC3};
 use enumflags2::{bitflags, BitFlags};
+use mips_mcu::fmt::virt_to_phys;
 use mips_mcu::PhysicalAddress;
 
+/// Maximum number of bytes of a block transfer
+const MAX_BLOCK_SIZE: usize = 0xffff;
+
+// bit masks and positions for DCRCCON
+const DCRCCON_BITO: u32 = 1 << 24;
+const DCRCCON_PLEN_POS: u32 = 8;
+const DCRCCON_CRCEN: u32 = 1 << 7;
+const DCRCCON_CRCAPP: u32 = 1 << 6;
+
 /// Interrupt flag or enable bits related to a specific DMA channel
 #[bitflags]
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -36,10 +54,118 @@ pub enum XferMode {
     Auto,
 }
 
+/// Parameters of a CRC calculated by the CRC generator of the DMA controller
+///
+/// The parameters follow the usual CRC catalogue notation. The presets have not
+/// been verified against the check values of the catalogues on hardware.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Crc {
+    /// Width of the CRC in bits (1 to 32)
+    pub width: u8,
+    /// Generator polynomial without the most significant bit
+    pub polynomial: u32,
+    /// Initial value (seed)
+    ///
+    /// DCRCDATA is loaded with this value as is. The CRC generator feeds each
+    /// data bit into the feedback of the shift register without appending
+    /// zero bits, so that this is the direct initial value as listed in CRC
+    /// catalogues (e.g. 0xffff for CRC-16/CCITT-FALSE) and not the equivalent
+    /// non-direct value (0x84cf for CRC-16/CCITT-FALSE).
+    pub init: u32,
+    /// Process the data least significant bit first (BITO) and reflect the
+    /// result
+    pub reflect: bool,
+    /// Value XORed to the result
+    pub xor_out: u32,
+}
+
+/// CRC-16/CCITT-FALSE
+pub const CRC16_CCITT: Crc = Crc {
+    width: 16,
+    polynomial: 0x1021,
+    init: 0xffff,
+    reflect: false,
+    xor_out: 0,
+};
+
+/// CRC-32 as used by Ethernet and zlib
+pub const CRC32: Crc = Crc {
+    width: 32,
+    polynomial: 0x04c1_1db7,
+    init: 0xffff_ffff,
+    reflect: true,
+    xor_out: 0xffff_ffff,
+};
+
+/// Carry out a block transfer triggered by software and wait for its
+/// completion
+///
+/// The sizes must be in the range 1..=MAX_BLOCK_SIZE.
+fn transfer<C: Ops + ?Sized>(
+    ch: &mut C,
+    src: PhysicalAddress,
+    src_size: usize,
+    dst: PhysicalAddress,
+    dst_size: usize,
+) {
+    ch.disable();
+    ch.set_start_event(None);
+    ch.set_abort_event(None);
+    ch.set_abort_pattern(None);
+    ch.irq_enable(BitFlags::empty());
+    ch.clear_all_irq_flags();
+    ch.set_source(src, src_size);
+    ch.set_dest(dst, dst_size);
+    ch.set_cell_size(max(src_size, dst_size));
+    // the DMA controller accesses the memory behind the back of the compiler
+    compiler_fence(Ordering::SeqCst);
+    unsafe {
+        ch.enable(XferMode::OneShot);
+    }
+    ch.force();
+    // one-shot channels are disabled after the block transfer
+    while ch.is_enabled() {}
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Enable the CRC generator for the channel `ch`
+fn crc_start(ch: u8, crc: &Crc, append: bool) {
+    assert!((1..=32).contains(&crc.width), "invalid CRC width");
+    let dmac = unsafe { &*DMAC::ptr() };
+    let mut dcrccon = ((crc.width as u32 - 1) << DCRCCON_PLEN_POS) | DCRCCON_CRCEN | ch as u32;
+    if crc.reflect {
+        dcrccon |= DCRCCON_BITO;
+    }
+    if append {
+        dcrccon |= DCRCCON_CRCAPP;
+    }
+    unsafe {
+        dmac.dcrcxor.write(|w| w.bits(crc.polynomial));
+        dmac.dcrcdata.write(|w| w.bits(crc.init));
+        dmac.dcrccon.write(|w| w.bits(dcrccon));
+    }
+}
+
+/// Disable the CRC generator and return the result
+fn crc_finish(crc: &Crc) -> u32 {
+    let dmac = unsafe { &*DMAC::ptr() };
+    let mut result = dmac.dcrcdata.read().bits();
+    dmac.dcrccon.write(|w| unsafe { w.bits(0) });
+    let shift = 32 - crc.width as u32;
+    if crc.reflect {
+        // BITO only affects the order in which the data bits are processed
+        result = result.reverse_bits() >> shift;
+    }
+    (result ^ crc.xor_out) & (u32::MAX >> shift)
+}
+
 /// DMA Operations
 ///
 /// This trait defines operations that can be carried out by a DMAChannel
 pub trait Ops {
+    /// Number of the DMA channel
+    fn channel_number(&self) -> u8;
+
     /// Set source address and size of source block in bytes
     fn set_source(&mut self, addr: PhysicalAddress, size: usize);
 
@@ -98,6 +224,70 @@ pub trait Ops {
 
     /// Force a cell transfer
     fn force(&mut self);
+
+    /// Copy `src` to `dst`
+    ///
+    /// The number of bytes copied is the minimum of the slice lengths and is
+    /// returned. The function blocks until the transfer is complete.
+    fn copy(&mut self, src: &[u8], dst: &mut [u8]) -> usize {
+        let len = src.len().min(dst.len());
+        for offset in (0..len).step_by(MAX_BLOCK_SIZE) {
+            let size = (len - offset).min(MAX_BLOCK_SIZE);
+            let src = virt_to_phys(src[offset..].as_ptr() as *mut u8);
+            let dst = virt_to_phys(dst[offset..].as_mut_ptr());
+            transfer(self, src, size, dst, size);
+        }
+        len
+    }
+
+    /// Fill `dst` with `value`
+    ///
+    /// The function blocks until the transfer is complete.
+    fn fill(&mut self, dst: &mut [u8], value: u8) {
+        let mut value = value;
+        let src = virt_to_phys(&mut value as *mut u8);
+        for offset in (0..dst.len()).step_by(MAX_BLOCK_SIZE) {
+            let size = (dst.len() - offset).min(MAX_BLOCK_SIZE);
+            let dst = virt_to_phys(dst[offset..].as_mut_ptr());
+            transfer(self, src, 1, dst, size);
+        }
+    }
+
+    /// Calculate the CRC of `data`
+    ///
+    /// The CRC generator is operated in append mode, i.e. the data is only
+    /// read. There is one CRC generator per DMA controller, which must not be
+    /// used by several channels at the same time.
+    ///
+    /// Panics if the width of `crc` is not in the range 1 to 32.
+    fn crc(&mut self, crc: &Crc, data: &[u8]) -> u32 {
+        // in append mode, the DMA controller writes the CRC to the
+        // destination after each block transfer; the destination size is 1
+        // so that the block size is given by the source size
+        let mut scratch = 0u32;
+        let dst = virt_to_phys(&mut scratch as *mut u32);
+        crc_start(self.channel_number(), crc, true);
+        for chunk in data.chunks(MAX_BLOCK_SIZE) {
+            let src = virt_to_phys(chunk.as_ptr() as *mut u8);
+            transfer(self, src, chunk.len(), dst, 1);
+        }
+        crc_finish(crc)
+    }
+
+    /// Copy `src` to `dst` and calculate the CRC of the copied bytes
+    ///
+    /// The CRC generator is operated in background mode, i.e. it calculates
+    /// the CRC while the data is transferred. Returns the number of bytes
+    /// copied, which is the minimum of the slice lengths, and the CRC. There
+    /// is one CRC generator per DMA controller, which must not be used by
+    /// several channels at the same time.
+    ///
+    /// Panics if the width of `crc` is not in the range 1 to 32.
+    fn copy_with_crc(&mut self, crc: &Crc, src: &[u8], dst: &mut [u8]) -> (usize, u32) {
+        crc_start(self.channel_number(), crc, false);
+        let len = self.copy(src, dst);
+        (len, crc_finish(crc))
+    }
 }
 
 pub struct DmaChannel<D> {
@@ -105,8 +295,22 @@ pub struct DmaChannel<D> {
 }
 
 macro_rules! dma {
-    ($Id:ident, $Dmac:ident) => {
+    ($Id:ident, $Dmac:ident, $Num:expr, $Vector:ident, $Irq:ident) => {
+        impl PeripheralInterrupt for DmaChannel<$Dmac> {
+            type Event = Irq;
+
+            const VECTOR: Interrupt = Interrupt::$Vector;
+
+            fn interrupt_source(_event: Irq) -> InterruptSource {
+                InterruptSource::$Irq
+            }
+        }
+
         impl Ops for DmaChannel<$Dmac> {
+            fn channel_number(&self) -> u8 {
+                $Num
+            }
+
             fn set_source(&mut self, addr: PhysicalAddress, size: usize) {
                 unsafe {
                     self.ch.ssa.write(|w| w.bits(addr.address() as u32));
@@ -224,7 +428,7 @@ macro_rules! dma {
     };
 }
 
-dma!(channel0, DMAC0);
-dma!(channel1, DMAC1);
-dma!(channel2, DMAC2);
-dma!(channel3, DMAC3);
+dma!(channel0, DMAC0, 0, DMA_0, DMA0);
+dma!(channel1, DMAC1, 1, DMA_1, DMA1);
+dma!(channel2, DMAC2, 2, DMA_2, DMA2);
+dma!(channel3, DMAC3, 3, DMA_3, DMA3);
@@ -0,0 +1,126 @@
+//! External interrupts (INT0 ... INT4)
+//!
+//! INT0 is connected to a fixed pin whereas INT1 ... INT4 are virtual inputs
+//! that must be mapped to a physical pin by means of the PPS. An `ExtInt`
+//! takes the respective pin or `MappedPin`, configures the edge that triggers
+//! the interrupt and implements `int::PeripheralInterrupt` so that the
+//! interrupt can be enabled, checked and cleared in a type-checked manner, e.g.
+//!
+//! ```ignore
+//! let int1 = ExtInt::new(pin.map_pin(inputs.int1), Edge::Falling);
+//! int1.listen(&int, Irq, IPL2, ISL0);
+//! ```
+
+use crate::gpio;
+use crate::int::{Interrupt, InterruptSource, Irq, PeripheralInterrupt};
+use crate::pac::INT;
+use crate::pps::{input, MappedPin};
+
+/// Edge that triggers an external interrupt
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Edge {
+    /// Falling edge
+    Falling,
+    /// Rising edge
+    Rising,
+}
+
+/// Pin that can be used as an external interrupt input
+pub trait ExtIntPin {
+    /// Number of the external interrupt (0 for INT0 etc.)
+    const NUMBER: u8;
+
+    /// Interrupt vector of the external interrupt
+    const VECTOR: Interrupt;
+
+    /// Interrupt source of the external interrupt
+    const SOURCE: InterruptSource;
+}
+
+macro_rules! ext_int_pin {
+    ($Pin:ty, $number:expr, $irq:ident) => {
+        impl<P> ExtIntPin for MappedPin<P, $Pin> {
+            const NUMBER: u8 = $number;
+            const VECTOR: Interrupt = Interrupt::$irq;
+            const SOURCE: InterruptSource = InterruptSource::$irq;
+        }
+    };
+}
+
+ext_int_pin!(input::Int1, 1, EXTERNAL_1);
+ext_int_pin!(input::Int2, 2, EXTERNAL_2);
+ext_int_pin!(input::Int3, 3, EXTERNAL_3);
+ext_int_pin!(input::Int4, 4, EXTERNAL_4);
+
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx2x4fxxxb",
+))]
+impl<MODE> ExtIntPin for gpio::portb::RB7<MODE> {
+    const NUMBER: u8 = 0;
+    const VECTOR: Interrupt = Interrupt::EXTERNAL_0;
+    const SOURCE: InterruptSource = InterruptSource::EXTERNAL_0;
+}
+
+#[cfg(any(feature = "pic32mx37x", feature = "pic32mx47x"))]
+impl<MODE> ExtIntPin for gpio::portd::RD0<MODE> {
+    const NUMBER: u8 = 0;
+    const VECTOR: Interrupt = Interrupt::EXTERNAL_0;
+    const SOURCE: InterruptSource = InterruptSource::EXTERNAL_0;
+}
+
+/// External interrupt
+pub struct ExtInt<PIN> {
+    pin: PIN,
+}
+
+impl<PIN: ExtIntPin> ExtInt<PIN> {
+    /// Create a new `ExtInt` triggered by `edge`
+    ///
+    /// The interrupt is not enabled. Changing the edge may set the interrupt
+    /// flag. Therefore, the flag should be cleared before enabling the
+    /// interrupt, which is done by `PeripheralInterrupt::listen()`.
+    pub fn new(pin: PIN, edge: Edge) -> Self {
+        let mut ext_int = ExtInt { pin };
+        ext_int.set_edge(edge);
+        ext_int
+    }
+
+    /// Return the pin consuming the `ExtInt` instance
+    ///
+    /// The interrupt is not disabled.
+    pub fn free(self) -> PIN {
+        self.pin
+    }
+
+    /// Set the edge that triggers the interrupt (INTCON.INTxEP)
+    pub fn set_edge(&mut self, edge: Edge) {
+        let int = unsafe { &*INT::ptr() };
+        let mask = 1 << PIN::NUMBER;
+        match edge {
+            Edge::Falling => int.intconclr.write(|w| unsafe { w.bits(mask) }),
+            Edge::Rising => int.intconset.write(|w| unsafe { w.bits(mask) }),
+        }
+    }
+
+    /// Get the edge that triggers the interrupt
+    pub fn edge(&self) -> Edge {
+        let int = unsafe { &*INT::ptr() };
+        if int.intcon.read().bits() & (1 << PIN::NUMBER) != 0 {
+            Edge::Rising
+        } else {
+            Edge::Falling
+        }
+    }
+}
+
+impl<PIN: ExtIntPin> PeripheralInterrupt for ExtInt<PIN> {
+    type Event = Irq;
+
+    const VECTOR: Interrupt = PIN::VECTOR;
+
+    fn interrupt_source(_event: Irq) -> InterruptSource {
+        PIN::SOURCE
+    }
+}
@@ -1,6 +1,10 @@
 //! General Purpose Input / Output
 
 use core::marker::PhantomData;
+use core::ptr::{read_volatile, write_volatile};
+
+use embedded_hal::digital as eh;
+use embedded_hal_0_2::digital::v2 as eh02;
 
 /// Extension trait to split a GPIO peripheral in independent pins and registers
 pub trait GpioExt {
@@ -35,8 +39,215 @@ pub struct PushPull;
 /// Open drain output (type state)
 pub struct OpenDrain;
 
+/// GPIO port
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Port {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+}
+
+/// Identifier of a physical pin that can be evaluated at runtime
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PinId {
+    /// Port of the pin
+    pub port: Port,
+    /// Pin number within the port
+    pub pin: u8,
+}
+
+macro_rules! port_id {
+    (PORTA) => {
+        Port::A
+    };
+    (PORTB) => {
+        Port::B
+    };
+    (PORTC) => {
+        Port::C
+    };
+    (PORTD) => {
+        Port::D
+    };
+    (PORTE) => {
+        Port::E
+    };
+    (PORTF) => {
+        Port::F
+    };
+    (PORTG) => {
+        Port::G
+    };
+}
+
+// offsets of the registers relative to the base address of a port
+const ANSEL: usize = 0x00;
+const TRIS: usize = 0x10;
+const PORT: usize = 0x20;
+const LAT: usize = 0x30;
+const ODC: usize = 0x40;
+const CNPU: usize = 0x50;
+const CNPD: usize = 0x60;
+
+// offsets of the CLR, SET and INV registers relative to a register
+const CLR: usize = 0x04;
+const SET: usize = 0x08;
+const INV: usize = 0x0c;
+
+/// Access to the registers of a single pin selected at runtime
+#[derive(Clone, Copy, Debug)]
+struct PinRegs {
+    base: usize,
+    port: Port,
+    i: u8,
+}
+
+impl PinRegs {
+    fn read(&self, reg: usize) -> bool {
+        unsafe { read_volatile((self.base + reg) as *const u32) & (1 << self.i) != 0 }
+    }
+
+    fn write(&self, reg: usize) {
+        unsafe { write_volatile((self.base + reg) as *mut u32, 1 << self.i) };
+    }
+
+    fn set_bit(&self, reg: usize, on: bool) {
+        self.write(reg + if on { SET } else { CLR });
+    }
+
+    /// Configure as digital pin and set direction, open drain, pull-up and
+    /// pull-down
+    fn configure(&self, output: bool, open_drain: bool, pull_up: bool, pull_down: bool) {
+        self.write(ANSEL + CLR);
+        self.set_bit(TRIS, !output);
+        if output {
+            self.set_bit(ODC, open_drain);
+        }
+        self.set_bit(CNPU, pull_up);
+        self.set_bit(CNPD, pull_down);
+    }
+}
+
+/// Implement the embedded-hal digital traits for a type-erased pin type
+/// having a `regs()` method
+macro_rules! erased_pin_impl {
+    ($Pin:ident) => {
+        impl<MODE> eh02::OutputPin for $Pin<Output<MODE>> {
+            type Error = ();
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
+                self.regs().write($crate::gpio::LAT + $crate::gpio::SET);
+                Ok(())
+            }
+
+            fn set_low(&mut self) -> Result<(), Self::Error> {
+                self.regs().write($crate::gpio::LAT + $crate::gpio::CLR);
+                Ok(())
+            }
+        }
+
+        impl<MODE> eh02::StatefulOutputPin for $Pin<Output<MODE>> {
+            fn is_set_high(&self) -> Result<bool, Self::Error> {
+                Ok(self.regs().read($crate::gpio::LAT))
+            }
+
+            fn is_set_low(&self) -> Result<bool, Self::Error> {
+                Ok(!self.regs().read($crate::gpio::LAT))
+            }
+        }
+
+        impl<MODE> eh02::ToggleableOutputPin for $Pin<Output<MODE>> {
+            type Error = ();
+
+            fn toggle(&mut self) -> Result<(), Self::Error> {
+                self.regs().write($crate::gpio::LAT + $crate::gpio::INV);
+                Ok(())
+            }
+        }
+
+        impl<MODE> eh02::InputPin for $Pin<Input<MODE>> {
+            type Error = ();
+
+            fn is_high(&self) -> Result<bool, Self::Error> {
+                Ok(self.regs().read($crate::gpio::PORT))
+            }
+
+            fn is_low(&self) -> Result<bool, Self::Error> {
+                Ok(!self.regs().read($crate::gpio::PORT))
+            }
+        }
+
+        impl eh02::InputPin for $Pin<Output<OpenDrain>> {
+            type Error = ();
+
+            fn is_high(&self) -> Result<bool, Self::Error> {
+                Ok(self.regs().read($crate::gpio::PORT))
+            }
+
+            fn is_low(&self) -> Result<bool, Self::Error> {
+                Ok(!self.regs().read($crate::gpio::PORT))
+            }
+        }
+
+        impl<MODE> eh::ErrorType for $Pin<MODE> {
+            type Error = core::convert::Infallible;
+        }
+
+        impl<MODE> eh::InputPin for $Pin<Input<MODE>> {
+            fn is_high(&mut self) -> Result<bool, Self::Error> {
+                Ok(self.regs().read($crate::gpio::PORT))
+            }
+
+            fn is_low(&mut self) -> Result<bool, Self::Error> {
+                Ok(!self.regs().read($crate::gpio::PORT))
+            }
+        }
+
+        impl eh::InputPin for $Pin<Output<OpenDrain>> {
+            fn is_high(&mut self) -> Result<bool, Self::Error> {
+                Ok(self.regs().read($crate::gpio::PORT))
+            }
+
+            fn is_low(&mut self) -> Result<bool, Self::Error> {
+                Ok(!self.regs().read($crate::gpio::PORT))
+            }
+        }
+
+        impl<MODE> eh::OutputPin for $Pin<Output<MODE>> {
+            fn set_low(&mut self) -> Result<(), Self::Error> {
+                self.regs().write($crate::gpio::LAT + $crate::gpio::CLR);
+                Ok(())
+            }
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
+                self.regs().write($crate::gpio::LAT + $crate::gpio::SET);
+                Ok(())
+            }
+        }
+
+        impl<MODE> eh::StatefulOutputPin for $Pin<Output<MODE>> {
+            fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+                Ok(self.regs().read($crate::gpio::LAT))
+            }
+
+            fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+                Ok(!self.regs().read($crate::gpio::LAT))
+            }
+
+            fn toggle(&mut self) -> Result<(), Self::Error> {
+                self.regs().write($crate::gpio::LAT + $crate::gpio::INV);
+                Ok(())
+            }
+        }
+    };
+}
+
 macro_rules! port {
-    ($PORTX:ident, $portx:ident, [
+    ($PORTX:ident, $portx:ident, $CN_IRQ:ident, [
         $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty $(, $has_ansel:expr)?),)+
     ]) => {
         /// GPIO
@@ -47,12 +258,13 @@ macro_rules! port {
             use embedded_hal_0_2::digital::v2 as eh02;
             use embedded_hal::digital as eh;
             use crate::pac::$PORTX;
+            use crate::int::{Interrupt, InterruptSource, Irq, PeripheralInterrupt};
 
             #[allow(unused_imports)]
             use super::Analog;
             use super::{
                 Floating, GpioExt, Input, OpenDrain, Output,
-                PullDown, PullUp, PushPull,
+                PinId, Port, PullDown, PullUp, PushPull,
             };
 
             /// GPIO parts
@@ -61,6 +273,8 @@ macro_rules! port {
                     /// Pin
                     pub $pxi: $PXi<$MODE>,
                 )+
+                /// Change notification control
+                pub cn: ChangeNotifier,
             }
 
             impl GpioExt for $PORTX {
@@ -71,8 +285,200 @@ macro_rules! port {
                         $(
                             $pxi: $PXi { _mode: PhantomData },
                         )+
+                        cn: ChangeNotifier { _private: () },
+                    }
+                }
+            }
+
+            /// Change notification control of the port
+            ///
+            /// Change notification must be enabled for each pin to be
+            /// monitored and for the port as a whole. A change is detected when
+            /// the level of a pin differs from the level read the last time from
+            /// the port register (mismatch mode).
+            ///
+            /// The methods to read the changed pins take `&self` and use
+            /// atomic register accesses only so that they can be called from
+            /// an interrupt service routine. The interrupt flag should be
+            /// cleared after `take_changed()` has been called.
+            pub struct ChangeNotifier {
+                _private: (),
+            }
+
+            impl ChangeNotifier {
+                /// Enable the change notification of the port
+                pub fn enable(&mut self) {
+                    unsafe { (*$PORTX::ptr()).cnconset.write(|w| w.bits(1 << 15)) };
+                }
+
+                /// Disable the change notification of the port
+                pub fn disable(&mut self) {
+                    unsafe { (*$PORTX::ptr()).cnconclr.write(|w| w.bits(1 << 15)) };
+                }
+
+                /// Get a bit mask of the pins for which a change has been
+                /// detected
+                pub fn changed(&self) -> u32 {
+                    unsafe { (*$PORTX::ptr()).cnstat.read().bits() }
+                }
+
+                /// Get a bit mask of the pins for which a change has been
+                /// detected and reset the change detection
+                ///
+                /// The change detection is reset by reading the port register.
+                pub fn take_changed(&self) -> u32 {
+                    let cnstat = unsafe { (*$PORTX::ptr()).cnstat.read().bits() };
+                    let _ = unsafe { (*$PORTX::ptr()).port.read().bits() };
+                    cnstat
+                }
+            }
+
+            impl PeripheralInterrupt for ChangeNotifier {
+                type Event = Irq;
+
+                const VECTOR: Interrupt = Interrupt::CHANGE_NOTICE;
+
+                fn interrupt_source(_event: Irq) -> InterruptSource {
+                    InterruptSource::$CN_IRQ
+                }
+            }
+
+            /// Pin of this port with the pin number erased from the type
+            pub struct PXx<MODE> {
+                i: u8,
+                _mode: PhantomData<MODE>,
+            }
+
+            impl<MODE> PXx<MODE> {
+                fn regs(&self) -> super::PinRegs {
+                    super::PinRegs { base: $PORTX::ptr() as usize, port: port_id!($PORTX), i: self.i }
+                }
+
+                /// Runtime identifier of the pin
+                pub fn id(&self) -> PinId {
+                    PinId { port: port_id!($PORTX), pin: self.i }
+                }
+
+                /// Pin number within the port
+                pub fn pin_number(&self) -> u8 {
+                    self.i
+                }
+
+                /// Erases the port from the type
+                pub fn downgrade(self) -> super::Pin<MODE> {
+                    super::Pin { regs: self.regs(), _mode: PhantomData }
+                }
+            }
+
+            erased_pin_impl!(PXx);
+
+            /// Group of pins of this port that are read or written
+            /// simultaneously
+            ///
+            /// Bit `k` of a bus value corresponds to `pins[k]`. All pins are
+            /// written in a single bus cycle by means of the SET, CLR or INV
+            /// registers so that other pins of the port are not affected, even
+            /// if they are modified by an interrupt service routine.
+            pub struct ParallelBus<MODE, const N: usize> {
+                pins: [PXx<MODE>; N],
+                mask: u32,
+                // bit position of pins[0] if the pins are contiguous
+                shift: Option<u8>,
+            }
+
+            impl<MODE, const N: usize> ParallelBus<MODE, N> {
+                /// Create a new `ParallelBus` from a group of pins
+                pub fn new(pins: [PXx<MODE>; N]) -> Self {
+                    let mask = pins.iter().fold(0, |m, p| m | (1 << p.i));
+                    let first = pins.first().map(|p| p.i);
+                    let contiguous = pins.iter().zip(first.unwrap_or(0)..).all(|(p, i)| p.i == i);
+                    let shift = if contiguous { first } else { None };
+                    ParallelBus { pins, mask, shift }
+                }
+
+                /// Return the pins consuming the `ParallelBus`
+                pub fn free(self) -> [PXx<MODE>; N] {
+                    self.pins
+                }
+
+                /// Bit mask of the pins within the port
+                pub fn port_mask(&self) -> u32 {
+                    self.mask
+                }
+
+                fn to_port(&self, value: u32) -> u32 {
+                    match self.shift {
+                        Some(shift) => (value << shift) & self.mask,
+                        None => self.pins.iter().enumerate().fold(0, |bits, (k, p)| {
+                            bits | (((value >> k) & 1) << p.i)
+                        }),
+                    }
+                }
+
+                fn to_value(&self, bits: u32) -> u32 {
+                    match self.shift {
+                        Some(shift) => (bits & self.mask) >> shift,
+                        None => self.pins.iter().enumerate().fold(0, |value, (k, p)| {
+                            value | (((bits >> p.i) & 1) << k)
+                        }),
                     }
                 }
+
+                fn reg(offset: usize) -> *mut u32 {
+                    ($PORTX::ptr() as usize + offset) as *mut u32
+                }
+            }
+
+            impl<MODE, const N: usize> ParallelBus<Output<MODE>, N> {
+                /// Write a value to the bus
+                pub fn write(&mut self, value: u32) {
+                    let bits = self.to_port(value);
+                    unsafe {
+                        let lat = core::ptr::read_volatile(Self::reg(super::LAT));
+                        // toggle the pins that differ from the value
+                        core::ptr::write_volatile(
+                            Self::reg(super::LAT + super::INV),
+                            (lat ^ bits) & self.mask,
+                        );
+                    }
+                }
+
+                /// Set the pins corresponding to the one bits of `value`
+                pub fn set_bits(&mut self, value: u32) {
+                    let bits = self.to_port(value);
+                    unsafe { core::ptr::write_volatile(Self::reg(super::LAT + super::SET), bits) };
+                }
+
+                /// Clear the pins corresponding to the one bits of `value`
+                pub fn clear_bits(&mut self, value: u32) {
+                    let bits = self.to_port(value);
+                    unsafe { core::ptr::write_volatile(Self::reg(super::LAT + super::CLR), bits) };
+                }
+
+                /// Toggle the pins corresponding to the one bits of `value`
+                pub fn toggle_bits(&mut self, value: u32) {
+                    let bits = self.to_port(value);
+                    unsafe { core::ptr::write_volatile(Self::reg(super::LAT + super::INV), bits) };
+                }
+
+                /// Get the value last written to the bus
+                pub fn output_value(&self) -> u32 {
+                    self.to_value(unsafe { core::ptr::read_volatile(Self::reg(super::LAT)) })
+                }
+            }
+
+            impl<MODE, const N: usize> ParallelBus<Input<MODE>, N> {
+                /// Read the levels of all pins at the same time
+                pub fn read(&self) -> u32 {
+                    self.to_value(unsafe { core::ptr::read_volatile(Self::reg(super::PORT)) })
+                }
+            }
+
+            impl<const N: usize> ParallelBus<Output<OpenDrain>, N> {
+                /// Read the levels of all pins at the same time
+                pub fn read(&self) -> u32 {
+                    self.to_value(unsafe { core::ptr::read_volatile(Self::reg(super::PORT)) })
+                }
             }
 
             $(
@@ -82,6 +488,14 @@ macro_rules! port {
                 }
 
                 impl<MODE> $PXi<MODE> {
+                    /// Runtime identifier of the pin
+                    pub const ID: PinId = PinId { port: port_id!($PORTX), pin: $i };
+
+                    /// Erases the pin number from the type
+                    pub fn downgrade(self) -> PXx<MODE> {
+                        PXx { i: $i, _mode: PhantomData }
+                    }
+
                     /// Configures the pin to operate as a floating input pin
                     pub fn into_floating_input(
                         self,
@@ -181,6 +595,19 @@ macro_rules! port {
                     )?
                 }
 
+                impl<MODE> $PXi<Input<MODE>> {
+                    /// Enables / disables the change notification for this pin
+                    pub fn set_change_notification(&mut self, on: bool) {
+                        unsafe {
+                            if on {
+                                (*$PORTX::ptr()).cnenset.write(|w| w.bits(1 << $i));
+                            } else {
+                                (*$PORTX::ptr()).cnenclr.write(|w| w.bits(1 << $i));
+                            }
+                        }
+                    }
+                }
+
                 impl $PXi<Output<OpenDrain>> {
                     /// Enables / disables the internal pull up
                     pub fn internal_pull_up(&mut self, on: bool) {
@@ -318,3 +745,187 @@ macro_rules! port {
 }
 
 include!("gpio_tables.rs");
+
+/// Pin with the port and the pin number erased from the type
+///
+/// Allows to build arrays of pins of different ports.
+pub struct Pin<MODE> {
+    regs: PinRegs,
+    _mode: PhantomData<MODE>,
+}
+
+impl<MODE> Pin<MODE> {
+    fn regs(&self) -> PinRegs {
+        self.regs
+    }
+
+    /// Runtime identifier of the pin
+    pub fn id(&self) -> PinId {
+        PinId {
+            port: self.regs.port,
+            pin: self.regs.i,
+        }
+    }
+
+    /// Pin number within the port
+    pub fn pin_number(&self) -> u8 {
+        self.regs.i
+    }
+
+    /// Converts into a `DynamicPin` operating in mode `mode`
+    pub fn into_dynamic(self, mode: DynamicMode) -> DynamicPin {
+        let mut pin = DynamicPin {
+            regs: self.regs,
+            mode,
+        };
+        pin.set_mode(mode);
+        pin
+    }
+}
+
+erased_pin_impl!(Pin);
+
+/// Operating mode of a `DynamicPin`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DynamicMode {
+    /// Floating input
+    FloatingInput,
+    /// Pulled up input
+    PullUpInput,
+    /// Pulled down input
+    PullDownInput,
+    /// Push pull output
+    PushPullOutput,
+    /// Open drain output
+    OpenDrainOutput,
+}
+
+/// Error of a `DynamicPin` operation not supported in the current mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PinModeError;
+
+impl eh::Error for PinModeError {
+    fn kind(&self) -> eh::ErrorKind {
+        eh::ErrorKind::Other
+    }
+}
+
+/// Pin whose mode can be changed at runtime
+///
+/// Reading the pin level is possible in the input modes and in the open
+/// drain output mode. Setting the output level is possible in the output
+/// modes. Otherwise, a `PinModeError` is returned.
+pub struct DynamicPin {
+    regs: PinRegs,
+    mode: DynamicMode,
+}
+
+impl DynamicPin {
+    /// Current operating mode
+    pub fn mode(&self) -> DynamicMode {
+        self.mode
+    }
+
+    /// Change the operating mode
+    pub fn set_mode(&mut self, mode: DynamicMode) {
+        match mode {
+            DynamicMode::FloatingInput => self.regs.configure(false, false, false, false),
+            DynamicMode::PullUpInput => self.regs.configure(false, false, true, false),
+            DynamicMode::PullDownInput => self.regs.configure(false, false, false, true),
+            DynamicMode::PushPullOutput => self.regs.configure(true, false, false, false),
+            DynamicMode::OpenDrainOutput => self.regs.configure(true, true, false, false),
+        }
+        self.mode = mode;
+    }
+
+    /// Pin number within the port
+    pub fn pin_number(&self) -> u8 {
+        self.regs.i
+    }
+
+    fn is_output(&self) -> bool {
+        matches!(
+            self.mode,
+            DynamicMode::PushPullOutput | DynamicMode::OpenDrainOutput
+        )
+    }
+
+    /// Check if the pin level is high
+    pub fn is_high(&self) -> Result<bool, PinModeError> {
+        if self.mode == DynamicMode::PushPullOutput {
+            return Err(PinModeError);
+        }
+        Ok(self.regs.read(PORT))
+    }
+
+    /// Check if the pin level is low
+    pub fn is_low(&self) -> Result<bool, PinModeError> {
+        self.is_high().map(|high| !high)
+    }
+
+    /// Set the output level high
+    pub fn set_high(&mut self) -> Result<(), PinModeError> {
+        if !self.is_output() {
+            return Err(PinModeError);
+        }
+        self.regs.write(LAT + SET);
+        Ok(())
+    }
+
+    /// Set the output level low
+    pub fn set_low(&mut self) -> Result<(), PinModeError> {
+        if !self.is_output() {
+            return Err(PinModeError);
+        }
+        self.regs.write(LAT + CLR);
+        Ok(())
+    }
+}
+
+impl eh02::InputPin for DynamicPin {
+    type Error = PinModeError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        DynamicPin::is_high(self)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        DynamicPin::is_low(self)
+    }
+}
+
+impl eh02::OutputPin for DynamicPin {
+    type Error = PinModeError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        DynamicPin::set_high(self)
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        DynamicPin::set_low(self)
+    }
+}
+
+impl eh::ErrorType for DynamicPin {
+    type Error = PinModeError;
+}
+
+impl eh::InputPin for DynamicPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        DynamicPin::is_high(self)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        DynamicPin::is_low(self)
+    }
+}
+
+impl eh::OutputPin for DynamicPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        DynamicPin::set_low(self)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        DynamicPin::set_high(self)
+    }
+}
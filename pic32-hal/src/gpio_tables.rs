@@ -6,7 +6,7 @@
 // PORTB: ----------------aaaaddddddddaaaa
 //
 #[cfg(feature = "pic32mx1xxfxxxb")]
-port!(PORTA, porta, [
+port!(PORTA, porta, CHANGE_NOTICE_A, [
     RA0: (ra0, 0, Input<Analog>, true),
     RA1: (ra1, 1, Input<Analog>, true),
     RA2: (ra2, 2, Input<Floating>),
@@ -15,7 +15,7 @@ port!(PORTA, porta, [
 ]);
 
 #[cfg(feature = "pic32mx1xxfxxxb")]
-port!(PORTB, portb, [
+port!(PORTB, portb, CHANGE_NOTICE_B, [
     RB0: (rb0, 0, Input<Analog>, true),
     RB1: (rb1, 1, Input<Analog>, true),
     RB2: (rb2, 2, Input<Analog>, true),
@@ -41,7 +41,7 @@ port!(PORTB, portb, [
 // PORTB: ----------------aaa-ddddd-ddaaaa
 //
 #[cfg(feature = "pic32mx2xxfxxxb")]
-port!(PORTA, porta, [
+port!(PORTA, porta, CHANGE_NOTICE_A, [
     RA0: (ra0, 0, Input<Analog>, true),
     RA1: (ra1, 1, Input<Analog>, true),
     RA2: (ra2, 2, Input<Floating>),
@@ -50,7 +50,7 @@ port!(PORTA, porta, [
 ]);
 
 #[cfg(feature = "pic32mx2xxfxxxb")]
-port!(PORTB, portb, [
+port!(PORTB, portb, CHANGE_NOTICE_B, [
     RB0: (rb0, 0, Input<Analog>, true),
     RB1: (rb1, 1, Input<Analog>, true),
     RB2: (rb2, 2, Input<Analog>, true),
@@ -74,7 +74,7 @@ port!(PORTB, portb, [
 // PORTB: ----------------aaa---ddd-ddaaaa
 //
 #[cfg(feature = "pic32mx2x4fxxxb")]
-port!(PORTA, porta, [
+port!(PORTA, porta, CHANGE_NOTICE_A, [
     RA0: (ra0, 0, Input<Analog>, true),
     RA1: (ra1, 1, Input<Analog>, true),
     RA2: (ra2, 2, Input<Floating>),
@@ -83,7 +83,7 @@ port!(PORTA, porta, [
 ]);
 
 #[cfg(feature = "pic32mx2x4fxxxb")]
-port!(PORTB, portb, [
+port!(PORTB, portb, CHANGE_NOTICE_B, [
     RB0: (rb0, 0, Input<Analog>, true),
     RB1: (rb1, 1, Input<Analog>, true),
     RB2: (rb2, 2, Input<Analog>, true),
@@ -110,7 +110,7 @@ port!(PORTB, portb, [
 // PORTG: ----------------dddd--aaaa--dddd
 //
 #[cfg(feature = "pic32mx37x")]
-port!(PORTA, porta, [
+port!(PORTA, porta, CHANGE_NOTICE_A, [
     RA0: (ra0, 0, Input<Floating>),
     RA1: (ra1, 1, Input<Floating>),
     RA2: (ra2, 2, Input<Floating>),
@@ -126,7 +126,7 @@ port!(PORTA, porta, [
 ]);
 
 #[cfg(feature = "pic32mx37x")]
-port!(PORTB, portb, [
+port!(PORTB, portb, CHANGE_NOTICE_B, [
     RB0: (rb0, 0, Input<Analog>, true),
     RB1: (rb1, 1, Input<Analog>, true),
     RB2: (rb2, 2, Input<Analog>, true),
@@ -146,7 +146,7 @@ port!(PORTB, portb, [
 ]);
 
 #[cfg(feature = "pic32mx37x")]
-port!(PORTC, portc, [
+port!(PORTC, portc, CHANGE_NOTICE_C, [
     RC1: (rc1, 1, Input<Floating>),
     RC2: (rc2, 2, Input<Floating>),
     RC3: (rc3, 3, Input<Floating>),
@@ -158,7 +158,7 @@ port!(PORTC, portc, [
 ]);
 
 #[cfg(feature = "pic32mx37x")]
-port!(PORTD, portd, [
+port!(PORTD, portd, CHANGE_NOTICE_D, [
     RD0: (rd0, 0, Input<Floating>),
     RD1: (rd1, 1, Input<Analog>, true),
     RD2: (rd2, 2, Input<Analog>, true),
@@ -178,7 +178,7 @@ port!(PORTD, portd, [
 ]);
 
 #[cfg(feature = "pic32mx37x")]
-port!(PORTE, porte, [
+port!(PORTE, porte, CHANGE_NOTICE_E, [
     RE0: (re0, 0, Input<Floating>),
     RE1: (re1, 1, Input<Floating>),
     RE2: (re2, 2, Input<Analog>, true),
@@ -192,7 +192,7 @@ port!(PORTE, porte, [
 ]);
 
 #[cfg(feature = "pic32mx37x")]
-port!(PORTF, portf, [
+port!(PORTF, portf, CHANGE_NOTICE_F, [
     RF0: (rf0, 0, Input<Floating>),
     RF1: (rf1, 1, Input<Floating>),
     RF2: (rf2, 2, Input<Floating>),
@@ -207,7 +207,7 @@ port!(PORTF, portf, [
 ]);
 
 #[cfg(feature = "pic32mx37x")]
-port!(PORTG, portg, [
+port!(PORTG, portg, CHANGE_NOTICE_G, [
     RG0: (rg0, 0, Input<Floating>),
     RG1: (rg1, 1, Input<Floating>),
     RG2: (rg2, 2, Input<Floating>),
@@ -234,7 +234,7 @@ port!(PORTG, portg, [
 // PORTG: ----------------dddd--aaaa----dd
 //
 #[cfg(feature = "pic32mx47x")]
-port!(PORTA, porta, [
+port!(PORTA, porta, CHANGE_NOTICE_A, [
     RA0: (ra0, 0, Input<Floating>),
     RA1: (ra1, 1, Input<Floating>),
     RA2: (ra2, 2, Input<Floating>),
@@ -250,7 +250,7 @@ port!(PORTA, porta, [
 ]);
 
 #[cfg(feature = "pic32mx47x")]
-port!(PORTB, portb, [
+port!(PORTB, portb, CHANGE_NOTICE_B, [
     RB0: (rb0, 0, Input<Analog>, true),
     RB1: (rb1, 1, Input<Analog>, true),
     RB2: (rb2, 2, Input<Analog>, true),
@@ -270,7 +270,7 @@ port!(PORTB, portb, [
 ]);
 
 #[cfg(feature = "pic32mx47x")]
-port!(PORTC, portc, [
+port!(PORTC, portc, CHANGE_NOTICE_C, [
     RC1: (rc1, 1, Input<Floating>),
     RC2: (rc2, 2, Input<Floating>),
     RC3: (rc3, 3, Input<Floating>),
@@ -282,7 +282,7 @@ port!(PORTC, portc, [
 ]);
 
 #[cfg(feature = "pic32mx47x")]
-port!(PORTD, portd, [
+port!(PORTD, portd, CHANGE_NOTICE_D, [
     RD0: (rd0, 0, Input<Floating>),
     RD1: (rd1, 1, Input<Analog>, true),
     RD2: (rd2, 2, Input<Analog>, true),
@@ -302,7 +302,7 @@ port!(PORTD, portd, [
 ]);
 
 #[cfg(feature = "pic32mx47x")]
-port!(PORTE, porte, [
+port!(PORTE, porte, CHANGE_NOTICE_E, [
     RE0: (re0, 0, Input<Floating>),
     RE1: (re1, 1, Input<Floating>),
     RE2: (re2, 2, Input<Analog>, true),
@@ -316,7 +316,7 @@ port!(PORTE, porte, [
 ]);
 
 #[cfg(feature = "pic32mx47x")]
-port!(PORTF, portf, [
+port!(PORTF, portf, CHANGE_NOTICE_F, [
     RF0: (rf0, 0, Input<Floating>),
     RF1: (rf1, 1, Input<Floating>),
     RF2: (rf2, 2, Input<Floating>),
@@ -329,7 +329,7 @@ port!(PORTF, portf, [
 ]);
 
 #[cfg(feature = "pic32mx47x")]
-port!(PORTG, portg, [
+port!(PORTG, portg, CHANGE_NOTICE_G, [
     RG0: (rg0, 0, Input<Floating>),
     RG1: (rg1, 1, Input<Floating>),
     RG6: (rg6, 6, Input<Analog>, true),
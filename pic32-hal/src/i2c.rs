@@ -1,7 +1,7 @@
 //! I2C driver for PIC32
 
 use crate::dma;
-use crate::int::InterruptSource;
+use crate::int::{Interrupt, InterruptSource, PeripheralInterrupt};
 use crate::pac::{I2C1, I2C2};
 use crate::time::Hertz;
 use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource, Operation, SevenBitAddress};
@@ -35,6 +35,19 @@ impl embedded_hal::i2c::Error for Error {
     }
 }
 
+/// I2C interrupt events
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Event {
+    /// Bus collision
+    Bus,
+
+    /// Slave event
+    Slave,
+
+    /// Master event
+    Master,
+}
+
 /// An I2C driver for the PIC32.
 ///
 /// Contains primitives `transmit()`, `receive()`, `rstart()`, `stop()` that can
@@ -80,7 +93,21 @@ pub trait Ops {
 }
 
 macro_rules! i2c_impl {
-    ($Id:ident, $I2c:ident) => {
+    ($Id:ident, $I2c:ident, $Vector:ident, $Bus:ident, $Slave:ident, $Master:ident) => {
+        impl PeripheralInterrupt for I2c<$I2c> {
+            type Event = Event;
+
+            const VECTOR: Interrupt = Interrupt::$Vector;
+
+            fn interrupt_source(event: Event) -> InterruptSource {
+                match event {
+                    Event::Bus => InterruptSource::$Bus,
+                    Event::Slave => InterruptSource::$Slave,
+                    Event::Master => InterruptSource::$Master,
+                }
+            }
+        }
+
         impl I2c<$I2c> {
             /// Create a new I2C object
             pub fn $Id(i2c: $I2c, pb_clock: Hertz, fscl: Fscl) -> I2c<$I2c> {
@@ -102,6 +129,15 @@ macro_rules! i2c_impl {
                 }
             }
 
+            /// Stop or continue operation in Idle mode
+            pub fn set_stop_in_idle(&mut self, stop: bool) {
+                if stop {
+                    self.i2c.contset.write(|w| w.sidl().bit(true));
+                } else {
+                    self.i2c.contclr.write(|w| w.sidl().bit(true));
+                }
+            }
+
             /// Destroy I2C object and return i2c HAL object
             pub fn free(self) -> $I2c {
                 self.i2c
@@ -151,7 +187,7 @@ macro_rules! i2c_impl {
                 let trn = &self.i2c.trn as *const _ as *mut u32;
                 dma.set_dest(virt_to_phys(trn), 1);
                 dma.set_cell_size(1);
-                dma.set_start_event(Some(InterruptSource::I2C1_MASTER));
+                dma.set_start_event(Some(Self::interrupt_source(Event::Master)));
                 dma.enable(dma::XferMode::OneShot);
                 dma.force();
                 Ok(())
@@ -274,5 +310,5 @@ macro_rules! i2c_impl {
     };
 }
 
-i2c_impl!(i2c1, I2C1);
-i2c_impl!(i2c2, I2C2);
+i2c_impl!(i2c1, I2C1, I2C_1, I2C1_BUS, I2C1_SLAVE, I2C1_MASTER);
+i2c_impl!(i2c2, I2C2, I2C_2, I2C2_BUS, I2C2_SLAVE, I2C2_MASTER);
@@ -6,6 +6,9 @@ use crate::pac::INT;
 use crate::pac_crate::{Reg, RegisterSpec};
 use core::marker::PhantomData;
 use core::ptr::{read_volatile, write_volatile};
+use mips_mcu::interrupt;
+
+pub mod priority;
 
 /// Interrupt source (from PAC)
 ///
@@ -186,4 +189,103 @@ impl Int {
         let (reg, bitpos) = Self::byteaddr(iv, unsafe { &(*INT::ptr()).ipc0 });
         unsafe { Isl((read_volatile(reg) >> bitpos) as u8 & 0x03) }
     }
+
+    /// Get the number of shadow register sets implemented by the CPU
+    pub fn shadow_sets(&self) -> u8 {
+        // HSS field of the SRSCTL register
+        ((interrupt::read_srsctl() >> 26) & 0x0f) as u8
+    }
+
+    /// Get the interrupt priority level whose interrupts use the shadow
+    /// register set
+    ///
+    /// The priority level is IPL7 on the PIC32MX1xx/2xx devices. On the
+    /// PIC32MX37x/47x devices, it is selected by the FSRSSEL configuration
+    /// setting and cannot be changed at runtime. Returns `None` if the CPU
+    /// does not implement a shadow register set.
+    pub fn shadow_set_ipl(&self) -> Option<Ipl> {
+        if self.shadow_sets() == 0 {
+            return None;
+        }
+        #[cfg(any(
+            feature = "pic32mx1xxfxxxb",
+            feature = "pic32mx2xxfxxxb",
+            feature = "pic32mx2x4fxxxb",
+        ))]
+        let ipl = IPL7;
+        #[cfg(any(feature = "pic32mx37x", feature = "pic32mx47x"))]
+        let ipl = Ipl(((crate::devcfg::devcfg3() >> 16) & 0x07) as u8);
+        Some(ipl)
+    }
+
+    /// Initialize the stack of the shadow register set
+    ///
+    /// Interrupt handlers executed with the shadow register set do not use
+    /// the stack of the interrupted code. This function sets the stack pointer
+    /// of the shadow register set to the end of `stack`. Must be called before
+    /// interrupts using the shadow register set are enabled. Does nothing if
+    /// the CPU does not implement a shadow register set.
+    pub fn init_shadow_set_stack(&self, stack: &'static mut [u64]) {
+        if self.shadow_sets() == 0 {
+            return;
+        }
+        let top = stack.as_mut_ptr_range().end as *mut u32;
+        unsafe { interrupt::init_shadow_set(1, top) };
+    }
+}
+
+/// Interrupt event of a peripheral that has a single interrupt source
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Irq;
+
+/// Typed interrupt configuration of a peripheral driver
+///
+/// Associates a driver with its interrupt vector and with the interrupt
+/// sources of its events so that the interrupts of a peripheral can be
+/// configured without having to know the vector and source numbers, e.g.
+/// `uart.listen(&int, uart::Event::Rx, IPL3, ISL0)`.
+pub trait PeripheralInterrupt {
+    /// Interrupt events of the peripheral; each event has its own interrupt
+    /// source
+    type Event: Copy;
+
+    /// Interrupt vector shared by all events of the peripheral
+    const VECTOR: Interrupt;
+
+    /// Interrupt source of an event
+    fn interrupt_source(event: Self::Event) -> InterruptSource;
+
+    /// Set the interrupt priority and sub priority of the interrupt vector
+    fn set_interrupt_priority(&self, int: &Int, ipl: Ipl, isl: Isl) {
+        int.set_ipl(Self::VECTOR, ipl);
+        int.set_isl(Self::VECTOR, isl);
+    }
+
+    /// Enable the interrupt of an event
+    fn enable_interrupt(&self, int: &Int, event: Self::Event) {
+        int.ei(Self::interrupt_source(event));
+    }
+
+    /// Disable the interrupt of an event
+    fn disable_interrupt(&self, int: &Int, event: Self::Event) {
+        int.di(Self::interrupt_source(event));
+    }
+
+    /// Check if the interrupt flag of an event is set
+    fn is_interrupt_pending(&self, int: &Int, event: Self::Event) -> bool {
+        int.get_if(Self::interrupt_source(event))
+    }
+
+    /// Clear the interrupt flag of an event
+    fn clear_interrupt(&self, int: &Int, event: Self::Event) {
+        int.clear_if(Self::interrupt_source(event));
+    }
+
+    /// Set the interrupt priority, clear the interrupt flag and enable the
+    /// interrupt of an event
+    fn listen(&self, int: &Int, event: Self::Event, ipl: Ipl, isl: Isl) {
+        self.set_interrupt_priority(int, ipl, isl);
+        self.clear_interrupt(int, event);
+        self.enable_interrupt(int, event);
+    }
 }
@@ -0,0 +1,102 @@
+//! Priority-ceiling critical sections
+//!
+//! `critical_section::with()` disables all interrupts. The functions and types
+//! of this module instead raise the interrupt priority level (IPL) of the CPU
+//! to a ceiling so that only interrupts having a priority equal to or lower
+//! than the ceiling are blocked. Interrupts of higher priority are still
+//! serviced without additional latency.
+//!
+//! A resource shared between contexts running at different priority levels
+//! must be protected by a ceiling that is equal to the highest priority level
+//! of these contexts. `Mutex` checks at runtime that it is not accessed from a
+//! context running at a priority level higher than its ceiling.
+
+use super::Ipl;
+use core::cell::{Cell, UnsafeCell};
+use mips_mcu::interrupt;
+
+/// Get the current interrupt priority level of the CPU
+pub fn current_ipl() -> Ipl {
+    Ipl(interrupt::ipl())
+}
+
+/// Execute closure `f` with the interrupt priority level of the CPU raised to
+/// `ceiling`
+///
+/// The interrupt priority level is not changed if it is already equal to or
+/// higher than `ceiling`. The previous interrupt priority level is restored
+/// afterwards.
+pub fn with_ceiling<R>(ceiling: Ipl, f: impl FnOnce() -> R) -> R {
+    let previous = interrupt::raise_ipl(ceiling.0);
+    let r = f();
+    if previous < ceiling.0 {
+        unsafe { interrupt::set_ipl(previous) };
+    }
+    r
+}
+
+/// A mutual exclusion primitive protecting data by means of a priority ceiling
+///
+/// The data can be accessed from all contexts running at a priority level
+/// equal to or lower than the ceiling, including the main thread (IPL0).
+pub struct Mutex<T> {
+    ceiling: Ipl,
+    locked: Cell<bool>,
+    data: UnsafeCell<T>,
+}
+
+// The data is accessed only while the interrupt priority level is raised to
+// the ceiling and nested locking is prevented.
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Create a new `Mutex` with a given priority ceiling
+    pub const fn new(ceiling: Ipl, data: T) -> Self {
+        Mutex {
+            ceiling,
+            locked: Cell::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Get the priority ceiling
+    pub fn ceiling(&self) -> Ipl {
+        self.ceiling
+    }
+
+    /// Lock the `Mutex` and execute closure `f` with a mutable reference to
+    /// the protected data
+    ///
+    /// Panics if called from a context running at a priority level higher
+    /// than the ceiling or if the `Mutex` is already locked by the current
+    /// context.
+    pub fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let previous = interrupt::raise_ipl(self.ceiling.0);
+        // the IPL is not changed when already above the ceiling
+        assert!(
+            previous <= self.ceiling.0,
+            "Mutex locked above its priority ceiling"
+        );
+        if self.locked.get() {
+            unsafe { interrupt::set_ipl(previous) };
+            panic!("Mutex already locked");
+        }
+        self.locked.set(true);
+        let r = f(unsafe { &mut *self.data.get() });
+        self.locked.set(false);
+        unsafe { interrupt::set_ipl(previous) };
+        r
+    }
+
+    /// Get a mutable reference to the protected data
+    ///
+    /// No locking is required because the `Mutex` is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consume the `Mutex` and return the protected data
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
@@ -45,16 +45,26 @@ pub use pic32mx470::pic32mx47xfxxxl as pac;
 pub mod adc;
 pub mod clock;
 pub mod coretimer;
+#[cfg(feature = "pic32mx2x4fxxxb")]
+pub mod deep_sleep;
+mod devcfg;
 pub mod dma;
+pub mod exti;
 pub mod gpio;
 pub mod i2c;
 pub mod int;
+pub mod nvm;
 pub mod oc;
+pub mod power;
 pub mod pps;
+pub mod reset;
+pub mod rtcc;
 pub mod spi;
+mod syskey;
 pub mod time;
 pub mod timer;
 pub mod uart;
+pub mod watchdog;
 
 #[cfg(any(
     feature = "pic32mx2xxfxxxb",
@@ -0,0 +1,272 @@
+//! Flash memory self-programming (NVM controller)
+//!
+//! The program flash memory is erased in pages and programmed in words or
+//! rows. The source of a row programming operation (NVMSRCADDR) must be
+//! located in RAM, so that rows are copied to a buffer on the stack before
+//! programming. All addresses are offsets relative to the beginning of the
+//! program flash memory.
+//!
+//! Quad-word programming is not supported because none of the supported
+//! devices provides it: the NVM controllers of the PIC32MX1xx/2xx, PIC32MX2x4
+//! and PIC32MX37x/47x have a single NVMDATA register and implement only word
+//! and row programming.
+//!
+//! Each operation is started by the NVM unlock sequence, which is carried out
+//! with interrupts disabled and the DMA controller suspended. The CPU stalls
+//! while the flash memory is busy so that the code may be executed from flash.
+//!
+//! `Nvm` implements the `NorFlash` trait of the `embedded-storage` crate.
+
+use core::hint::black_box;
+use core::ptr::read_volatile;
+
+use crate::pac::{BMX, DMAC, NVM};
+use embedded_storage::nor_flash::{
+    check_erase, check_read, check_write, ErrorType, NorFlash, NorFlashError, NorFlashErrorKind,
+    ReadNorFlash,
+};
+use mips_mcu::fmt::virt_to_phys;
+
+/// Size of a flash page in bytes (smallest erasable unit)
+#[cfg(any(feature = "pic32mx1xxfxxxb", feature = "pic32mx2xxfxxxb"))]
+pub const PAGE_SIZE: usize = 1024;
+#[cfg(feature = "pic32mx2x4fxxxb")]
+pub const PAGE_SIZE: usize = 2048;
+#[cfg(any(feature = "pic32mx37x", feature = "pic32mx47x"))]
+pub const PAGE_SIZE: usize = 4096;
+
+/// Size of a flash row in bytes
+#[cfg(any(feature = "pic32mx1xxfxxxb", feature = "pic32mx2xxfxxxb"))]
+pub const ROW_SIZE: usize = 128;
+#[cfg(feature = "pic32mx2x4fxxxb")]
+pub const ROW_SIZE: usize = 256;
+#[cfg(any(feature = "pic32mx37x", feature = "pic32mx47x"))]
+pub const ROW_SIZE: usize = 512;
+
+/// Number of 32-bit words per row
+pub const ROW_WORDS: usize = ROW_SIZE / 4;
+
+/// Physical address of the program flash memory
+const PFM_PHYS_ADDR: u32 = 0x1d00_0000;
+
+/// Virtual address of the program flash memory (KSEG1, uncached)
+const PFM_KSEG1_ADDR: usize = 0xbd00_0000;
+
+// bit masks for NVMCON
+const NVMCON_WR: u32 = 0x8000;
+const NVMCON_WREN: u32 = 0x4000;
+const NVMCON_WRERR: u32 = 0x2000;
+const NVMCON_LVDERR: u32 = 0x1000;
+
+// NVM operations (NVMOP)
+const NVMOP_NOP: u32 = 0b0000;
+const NVMOP_WORD_PROGRAM: u32 = 0b0001;
+const NVMOP_ROW_PROGRAM: u32 = 0b0011;
+const NVMOP_PAGE_ERASE: u32 = 0b0100;
+
+/// SUSPEND bit of DMACON
+const DMACON_SUSPEND: u32 = 0x1000;
+
+/// NVM errors
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// Low-voltage detected during the operation (LVDERR)
+    LowVoltage,
+    /// The operation failed (WRERR), e.g. because the address is
+    /// write-protected
+    Write,
+    /// The address or length is not aligned to the operation
+    NotAligned,
+    /// The address or length exceeds the flash memory
+    OutOfBounds,
+}
+
+impl NorFlashError for Error {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Error::NotAligned => NorFlashErrorKind::NotAligned,
+            Error::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl From<NorFlashErrorKind> for Error {
+    fn from(kind: NorFlashErrorKind) -> Self {
+        match kind {
+            NorFlashErrorKind::NotAligned => Error::NotAligned,
+            _ => Error::OutOfBounds,
+        }
+    }
+}
+
+/// Flash memory self-programming driver
+pub struct Nvm {
+    nvm: NVM,
+    size: usize,
+}
+
+impl Nvm {
+    /// Create a new `Nvm` instance
+    ///
+    /// The size of the program flash memory is read from BMXPFMSZ.
+    pub fn new(nvm: NVM) -> Self {
+        let size = unsafe { (*BMX::ptr()).bmxpfmsz.read().bits() } as usize;
+        Nvm { nvm, size }
+    }
+
+    /// Return the NVM peripheral consuming the `Nvm` instance
+    pub fn free(self) -> NVM {
+        self.nvm
+    }
+
+    /// Size of the program flash memory in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Check that `len` bytes at `offset` are within the flash memory and
+    /// aligned to `align`
+    fn check(&self, offset: u32, len: usize, align: usize) -> Result<(), Error> {
+        if !(offset as usize).is_multiple_of(align) || !len.is_multiple_of(align) {
+            Err(Error::NotAligned)
+        } else if offset as usize + len > self.size {
+            Err(Error::OutOfBounds)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Start an operation by means of the unlock sequence and wait for its
+    /// completion
+    fn unlock_and_execute(&mut self, op: u32) -> u32 {
+        critical_section::with(|_| {
+            let dmac = unsafe { &*DMAC::ptr() };
+            let suspended = dmac.dmacon.read().bits() & DMACON_SUSPEND != 0;
+            dmac.dmaconset.write(|w| unsafe { w.bits(DMACON_SUSPEND) });
+            self.nvm
+                .nvmcon
+                .write(|w| unsafe { w.bits(NVMCON_WREN | op) });
+            self.nvm.nvmkey.write(|w| unsafe { w.bits(0xaa99_6655) });
+            self.nvm.nvmkey.write(|w| unsafe { w.bits(0x5566_99aa) });
+            self.nvm.nvmconset.write(|w| unsafe { w.bits(NVMCON_WR) });
+            while self.nvm.nvmcon.read().bits() & NVMCON_WR != 0 {}
+            self.nvm.nvmconclr.write(|w| unsafe { w.bits(NVMCON_WREN) });
+            if !suspended {
+                dmac.dmaconclr.write(|w| unsafe { w.bits(DMACON_SUSPEND) });
+            }
+        });
+        self.nvm.nvmcon.read().bits()
+    }
+
+    /// Execute the operation `op` at `offset`
+    fn execute(&mut self, op: u32, offset: u32) -> Result<(), Error> {
+        self.nvm
+            .nvmaddr
+            .write(|w| unsafe { w.bits(PFM_PHYS_ADDR + offset) });
+        let nvmcon = self.unlock_and_execute(op);
+        if nvmcon & (NVMCON_WRERR | NVMCON_LVDERR) == 0 {
+            return Ok(());
+        }
+        // the error flags are cleared by a NOP operation
+        self.unlock_and_execute(NVMOP_NOP);
+        if nvmcon & NVMCON_LVDERR != 0 {
+            Err(Error::LowVoltage)
+        } else {
+            Err(Error::Write)
+        }
+    }
+
+    /// Erase the page at `offset`
+    pub fn erase_page(&mut self, offset: u32) -> Result<(), Error> {
+        self.check(offset, PAGE_SIZE, PAGE_SIZE)?;
+        self.execute(NVMOP_PAGE_ERASE, offset)
+    }
+
+    /// Program a word at `offset`
+    pub fn write_word(&mut self, offset: u32, word: u32) -> Result<(), Error> {
+        self.check(offset, 4, 4)?;
+        self.nvm.nvmdata.write(|w| unsafe { w.bits(word) });
+        self.execute(NVMOP_WORD_PROGRAM, offset)
+    }
+
+    /// Program a row at `offset`
+    pub fn write_row(&mut self, offset: u32, row: &[u32; ROW_WORDS]) -> Result<(), Error> {
+        self.check(offset, ROW_SIZE, ROW_SIZE)?;
+        // the source may be located in flash memory; the copy is only read by
+        // the NVM controller and must therefore not be optimized away
+        let mut buf = *row;
+        let src = virt_to_phys(black_box(&mut buf).as_mut_ptr()).address() as u32;
+        self.nvm.nvmsrcaddr.write(|w| unsafe { w.bits(src) });
+        self.execute(NVMOP_ROW_PROGRAM, offset)
+    }
+
+    /// Read bytes starting at `offset`
+    pub fn read(&self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+        self.check(offset, bytes.len(), 1)?;
+        let src = (PFM_KSEG1_ADDR + offset as usize) as *const u8;
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = unsafe { read_volatile(src.add(i)) };
+        }
+        Ok(())
+    }
+}
+
+/// Convert 4 bytes to a word
+fn word(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl ErrorType for Nvm {
+    type Error = Error;
+}
+
+impl ReadNorFlash for Nvm {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+        check_read(self, offset, bytes.len())?;
+        Nvm::read(self, offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        self.size
+    }
+}
+
+impl NorFlash for Nvm {
+    const WRITE_SIZE: usize = 4;
+
+    const ERASE_SIZE: usize = PAGE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Error> {
+        check_erase(self, from, to)?;
+        for offset in (from..to).step_by(PAGE_SIZE) {
+            self.erase_page(offset)?;
+        }
+        Ok(())
+    }
+
+    /// Program full rows where possible and words otherwise
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+        check_write(self, offset, bytes.len())?;
+        let mut offset = offset;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            let len = if (offset as usize).is_multiple_of(ROW_SIZE) && bytes.len() >= ROW_SIZE {
+                let mut row = [0; ROW_WORDS];
+                for (w, chunk) in row.iter_mut().zip(bytes.chunks_exact(4)) {
+                    *w = word(chunk);
+                }
+                self.write_row(offset, &row)?;
+                ROW_SIZE
+            } else {
+                self.write_word(offset, word(bytes))?;
+                Self::WRITE_SIZE
+            };
+            offset += len as u32;
+            bytes = &bytes[len..];
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! Power-saving modes and Peripheral Module Disable (PMD)
+//!
+//! The MIPS `wait` instruction puts the CPU either into Sleep or into Idle
+//! mode depending on the SLPEN bit of the OSCCON register. `sleep()` and
+//! `idle()` set this bit accordingly before executing `wait`. `sleep()` clears
+//! SLPEN again after wake-up so that a `wait` executed elsewhere (e.g. by
+//! `mips_mcu::interrupt::wait()`) enters Idle mode as after reset. In Idle
+//! mode, the peripherals continue to operate unless configured to stop in Idle
+//! mode (see the `set_stop_in_idle()` methods of the peripheral drivers).
+
+use crate::devcfg;
+use crate::pac::CFG;
+use crate::syskey;
+use core::ptr::{read_volatile, write_volatile};
+use mips_mcu::interrupt;
+
+#[cfg(feature = "pic32mx2x4fxxxb")]
+use crate::pac::CRU as OSC;
+
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+use crate::pac::RCON;
+
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+use crate::pac::OSC;
+
+/// PMDL1WAY bit of DEVCFG3
+const DEVCFG3_PMDL1WAY: u32 = 1 << 28;
+
+/// Power management errors
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Error {
+    /// The PMD registers are locked and cannot be unlocked because of the
+    /// PMDL1WAY configuration setting
+    Locked,
+}
+
+fn set_slpen(slpen: bool) {
+    let osc = unsafe { &*OSC::ptr() };
+    critical_section::with(|_| {
+        unsafe { syskey::unlock() };
+        if slpen {
+            osc.oscconset.write(|w| w.slpen().bit(true));
+        } else {
+            osc.oscconclr.write(|w| w.slpen().bit(true));
+        }
+        syskey::lock();
+    });
+}
+
+/// Enter Sleep mode
+///
+/// The system clock and most peripherals are stopped. The CPU wakes up when
+/// an enabled interrupt occurs. The interrupt is serviced if its priority is
+/// higher than the current CPU priority and if interrupts are enabled.
+pub fn sleep() {
+    set_slpen(true);
+    interrupt::wait();
+    set_slpen(false);
+}
+
+/// Enter Idle mode
+///
+/// The CPU is halted but the system clock keeps running and the peripherals
+/// continue to operate unless configured to stop in Idle mode.
+pub fn idle() {
+    set_slpen(false);
+    interrupt::wait();
+}
+
+/// Enable or disable the voltage regulator standby mode during Sleep
+///
+/// When enabled, the voltage regulator goes into a low power standby mode
+/// during Sleep, which reduces the Sleep current but increases the wake-up
+/// time.
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+pub fn set_regulator_standby(standby: bool) {
+    let rcon = unsafe { &*RCON::ptr() };
+    if standby {
+        rcon.rconset.write(|w| w.vregs().bit(true));
+    } else {
+        rcon.rconclr.write(|w| w.vregs().bit(true));
+    }
+}
+
+/// Enable or disable the voltage regulator standby mode during Sleep
+///
+/// When enabled, the voltage regulator goes into a low power standby mode
+/// during Sleep, which reduces the Sleep current but increases the wake-up
+/// time.
+#[cfg(feature = "pic32mx2x4fxxxb")]
+pub fn set_regulator_standby(standby: bool) {
+    // VREGS is located in PWRCON of the clock and reset unit
+    let cru = unsafe { &*OSC::ptr() };
+    if standby {
+        cru.pwrconset.write(|w| w.vregs().bit(true));
+    } else {
+        cru.pwrconclr.write(|w| w.vregs().bit(true));
+    }
+}
+
+/// Peripheral modules that can be disabled by means of the PMD registers
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Module {
+    /// ADC
+    Adc1,
+    /// Charge Time Measurement Unit (CTMU)
+    Ctmu,
+    /// Comparator voltage reference
+    Cvr,
+    /// Comparator 1
+    Cmp1,
+    /// Comparator 2
+    Cmp2,
+    /// Comparator 3
+    Cmp3,
+    /// Input Capture 1
+    Ic1,
+    /// Input Capture 2
+    Ic2,
+    /// Input Capture 3
+    Ic3,
+    /// Input Capture 4
+    Ic4,
+    /// Input Capture 5
+    Ic5,
+    /// Output Compare 1
+    Oc1,
+    /// Output Compare 2
+    Oc2,
+    /// Output Compare 3
+    Oc3,
+    /// Output Compare 4
+    Oc4,
+    /// Output Compare 5
+    Oc5,
+    /// Timer 1
+    Timer1,
+    /// Timer 2
+    Timer2,
+    /// Timer 3
+    Timer3,
+    /// Timer 4
+    Timer4,
+    /// Timer 5
+    Timer5,
+    /// UART 1
+    Uart1,
+    /// UART 2
+    Uart2,
+    /// SPI 1
+    Spi1,
+    /// SPI 2
+    Spi2,
+    /// I2C 1
+    I2c1,
+    /// I2C 2
+    I2c2,
+    /// USB
+    #[cfg(any(
+        feature = "pic32mx2xxfxxxb",
+        feature = "pic32mx2x4fxxxb",
+        feature = "pic32mx47x",
+    ))]
+    Usb,
+    /// Real-Time Clock and Calendar (RTCC)
+    Rtcc,
+    /// Reference clock output
+    Refclock,
+    /// Parallel Master Port (PMP)
+    Pmp,
+}
+
+impl Module {
+    /// PMD register index (0 for PMD1) and bit position
+    fn location(self) -> (usize, u32) {
+        match self {
+            Module::Adc1 => (0, 0),
+            Module::Ctmu => (0, 8),
+            Module::Cvr => (0, 12),
+            Module::Cmp1 => (1, 0),
+            Module::Cmp2 => (1, 1),
+            Module::Cmp3 => (1, 2),
+            Module::Ic1 => (2, 0),
+            Module::Ic2 => (2, 1),
+            Module::Ic3 => (2, 2),
+            Module::Ic4 => (2, 3),
+            Module::Ic5 => (2, 4),
+            Module::Oc1 => (2, 16),
+            Module::Oc2 => (2, 17),
+            Module::Oc3 => (2, 18),
+            Module::Oc4 => (2, 19),
+            Module::Oc5 => (2, 20),
+            Module::Timer1 => (3, 0),
+            Module::Timer2 => (3, 1),
+            Module::Timer3 => (3, 2),
+            Module::Timer4 => (3, 3),
+            Module::Timer5 => (3, 4),
+            Module::Uart1 => (4, 0),
+            Module::Uart2 => (4, 1),
+            Module::Spi1 => (4, 8),
+            Module::Spi2 => (4, 9),
+            Module::I2c1 => (4, 16),
+            Module::I2c2 => (4, 17),
+            #[cfg(any(
+                feature = "pic32mx2xxfxxxb",
+                feature = "pic32mx2x4fxxxb",
+                feature = "pic32mx47x",
+            ))]
+            Module::Usb => (4, 24),
+            Module::Rtcc => (5, 0),
+            Module::Refclock => (5, 1),
+            Module::Pmp => (5, 16),
+        }
+    }
+}
+
+/// Peripheral Module Disable (PMD) control
+///
+/// A disabled peripheral module does not consume power and its registers
+/// cannot be accessed. The PMD registers can be locked. Depending on the
+/// PMDL1WAY configuration setting, the lock is permanent until the next
+/// reset. The PMD registers are part of the CFG peripheral.
+pub struct Pmd {
+    cfg: CFG,
+}
+
+impl Pmd {
+    /// Create a new `Pmd` instance
+    pub fn new(cfg: CFG) -> Self {
+        Pmd { cfg }
+    }
+
+    /// Return the CFG peripheral consuming the `Pmd` instance
+    pub fn free(self) -> CFG {
+        self.cfg
+    }
+
+    fn reg(&self, module: Module) -> (*mut u32, u32) {
+        let (regndx, bit) = module.location();
+        let mask = 1 << bit;
+        let base = &self.cfg.pmd1 as *const _ as usize;
+        ((base + regndx * 0x10) as *mut u32, mask)
+    }
+
+    /// Check if the PMD registers are locked
+    pub fn is_locked(&self) -> bool {
+        self.cfg.cfgcon.read().pmdlock().bit()
+    }
+
+    /// Lock the PMD registers
+    ///
+    /// When PMDL1WAY is set in the configuration words, the PMD registers
+    /// cannot be unlocked until the next reset.
+    pub fn lock(&mut self) {
+        critical_section::with(|_| {
+            unsafe { syskey::unlock() };
+            self.cfg.cfgcon.modify(|_, w| w.pmdlock().bit(true));
+            syskey::lock();
+        });
+    }
+
+    /// Unlock the PMD registers
+    ///
+    /// Returns a `Locked` error if the PMD registers are locked and PMDL1WAY
+    /// is set in the configuration words.
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        if !self.is_locked() {
+            return Ok(());
+        }
+        if devcfg::devcfg3() & DEVCFG3_PMDL1WAY != 0 {
+            return Err(Error::Locked);
+        }
+        critical_section::with(|_| {
+            unsafe { syskey::unlock() };
+            self.cfg.cfgcon.modify(|_, w| w.pmdlock().bit(false));
+            syskey::lock();
+        });
+        Ok(())
+    }
+
+    /// Check if a peripheral module is disabled
+    pub fn is_disabled(&self, module: Module) -> bool {
+        let (reg, mask) = self.reg(module);
+        unsafe { read_volatile(reg) & mask != 0 }
+    }
+
+    /// Disable a peripheral module
+    ///
+    /// Unlocks the PMD registers if needed. The lock state is not restored.
+    /// Returns a `Locked` error if the PMD registers cannot be unlocked.
+    pub fn disable(&mut self, module: Module) -> Result<(), Error> {
+        self.unlock()?;
+        let (reg, mask) = self.reg(module);
+        // write to the SET register
+        unsafe { write_volatile(reg.add(2), mask) };
+        Ok(())
+    }
+
+    /// Enable a peripheral module
+    ///
+    /// Unlocks the PMD registers if needed. The lock state is not restored.
+    /// Returns a `Locked` error if the PMD registers cannot be unlocked. The
+    /// peripheral module is reset to its default state when being enabled.
+    pub fn enable(&mut self, module: Module) -> Result<(), Error> {
+        self.unlock()?;
+        let (reg, mask) = self.reg(module);
+        // write to the CLR register
+        unsafe { write_volatile(reg.add(1), mask) };
+        Ok(())
+    }
+}
@@ -1,7 +1,14 @@
 //! Peripheral Pin Select (PPS)
+//!
+//! Pins can be mapped at compile time by means of `MappedPin`, which checks
+//! the mapping with the type system, or at runtime by means of `DynamicPps`,
+//! which validates a mapping against the same tables and returns an error for
+//! impossible combinations.
 
-use crate::gpio;
-use crate::pac::PPS;
+use crate::devcfg;
+use crate::gpio::{self, PinId};
+use crate::pac::{CFG, PPS};
+use crate::syskey;
 
 /// Extension trait to split a PPS peripheral into Parts corresponding to
 /// virtual ports
@@ -172,6 +179,289 @@ macro_rules! pps_tables {
             }
         )+
     )+
+
+    /// Virtual input functions that can be mapped at runtime
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum InputFunction {
+        $(
+            $IDTYPE,
+        )+
+    }
+
+    /// Virtual output functions that can be mapped at runtime
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum OutputFunction {
+        $(
+            $ODTYPE,
+        )+
+    }
+
+    // DynamicPps tracks the mapped input functions in a u64
+    const _: () = assert!([$(InputFunction::$IDTYPE),+].len() <= 64);
+
+    /// Write the input mapping register; returns false if `pin` cannot be
+    /// mapped to `func`
+    fn write_input_mapping(func: InputFunction, pin: PinId) -> bool {
+        // the pin types of the tables are generic over MODE
+        #[allow(clippy::upper_case_acronyms)]
+        type MODE = ();
+        match func {
+            $(
+                InputFunction::$VINTYPE => {
+                    $(
+                        if pin == <$INPTYPE>::ID {
+                            unsafe {(*PPS::ptr()).$INREG.write(|w| w.$INREG().bits($INVAL))};
+                            return true;
+                        }
+                    )+
+                    false
+                }
+            )+
+        }
+    }
+
+    /// Write a value to the input mapping register of `func` that selects no
+    /// pin
+    fn reset_input_mapping(func: InputFunction) {
+        match func {
+            $(
+                InputFunction::$VINTYPE => {
+                    const UNUSED: u8 = unused_input_value(&[$($INVAL),+]);
+                    unsafe {(*PPS::ptr()).$INREG.write(|w| w.$INREG().bits(UNUSED))};
+                }
+            )+
+        }
+    }
+
+    /// Write the output mapping register of `pin`; `None` clears the
+    /// mapping. Returns false if `func` cannot be mapped to `pin`.
+    fn write_output_mapping(pin: PinId, func: Option<OutputFunction>) -> bool {
+        // the pin types of the tables are generic over MODE
+        #[allow(clippy::upper_case_acronyms)]
+        type MODE = ();
+        $(
+            if pin == <$OPTYPE>::ID {
+                let value = match func {
+                    None => 0,
+                    $(
+                        Some(OutputFunction::$VOUTTYPE) => $OVAL,
+                    )+
+                    #[allow(unreachable_patterns)]
+                    _ => return false,
+                };
+                unsafe { (*PPS::ptr()).$OREG.write(|w| w.$OREG().bits(value)) };
+                return true;
+            }
+        )+
+        false
+    }
 }}
 
+/// Largest value of an input mapping register that selects none of the pins in
+/// `values`. Values not assigned to a pin are reserved.
+const fn unused_input_value(values: &[u8]) -> u8 {
+    let mut value = 0x0f;
+    loop {
+        let mut i = 0;
+        while i < values.len() && values[i] != value {
+            i += 1;
+        }
+        if i == values.len() {
+            return value;
+        }
+        value -= 1;
+    }
+}
+
 include!("pps_tables.rs");
+
+/// IOL1WAY bit of DEVCFG3
+const DEVCFG3_IOL1WAY: u32 = 1 << 29;
+
+/// Errors of the runtime PPS mapping
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Error {
+    /// The pin cannot be mapped to the virtual function
+    InvalidMapping,
+    /// The virtual input function is already mapped to another pin
+    FunctionInUse,
+    /// The PPS registers are locked (IOLOCK) or cannot be unlocked because of
+    /// the IOL1WAY configuration setting
+    Locked,
+}
+
+/// Pin mapped at runtime to a virtual input function
+pub struct DynMappedInput<MODE> {
+    pin: gpio::Pin<MODE>,
+    func: InputFunction,
+}
+
+impl<MODE> DynMappedInput<MODE> {
+    /// The physical pin
+    pub fn pin(&self) -> &gpio::Pin<MODE> {
+        &self.pin
+    }
+
+    /// The virtual input function
+    pub fn function(&self) -> InputFunction {
+        self.func
+    }
+}
+
+/// Pin mapped at runtime to a virtual output function
+pub struct DynMappedOutput<MODE> {
+    pin: gpio::Pin<MODE>,
+    func: OutputFunction,
+}
+
+impl<MODE> DynMappedOutput<MODE> {
+    /// The physical pin
+    pub fn pin(&self) -> &gpio::Pin<MODE> {
+        &self.pin
+    }
+
+    /// The virtual output function
+    pub fn function(&self) -> OutputFunction {
+        self.func
+    }
+}
+
+/// Runtime PPS mapping
+///
+/// Maps type-erased pins (`gpio::Pin`) to virtual functions selected at
+/// runtime and validates each mapping against the PPS tables of the device.
+/// Consumes the virtual pins of the compile-time mapping API so that both APIs
+/// cannot be used at the same time. The physical pins are owned by the mapped
+/// pin objects and each virtual input function can be mapped to one pin only.
+///
+/// Mapping registers can be written only if the PPS registers are not locked
+/// (IOLOCK).
+pub struct DynamicPps {
+    parts: Parts,
+    inputs_used: u64,
+}
+
+impl DynamicPps {
+    /// Create a new `DynamicPps` from the virtual pins
+    pub fn new(parts: Parts) -> Self {
+        DynamicPps {
+            parts,
+            inputs_used: 0,
+        }
+    }
+
+    /// Return the virtual pins consuming the `DynamicPps`
+    ///
+    /// Existing mappings remain in effect.
+    pub fn free(self) -> Parts {
+        self.parts
+    }
+
+    /// Check if the PPS registers are locked
+    pub fn is_locked(&self) -> bool {
+        unsafe { (*CFG::ptr()).cfgcon.read().iolock().bit() }
+    }
+
+    /// Lock the PPS registers
+    ///
+    /// When IOL1WAY is set in the configuration words, the PPS registers
+    /// cannot be unlocked until the next reset.
+    pub fn lock(&mut self) {
+        critical_section::with(|_| {
+            unsafe { syskey::unlock() };
+            unsafe { (*CFG::ptr()).cfgcon.modify(|_, w| w.iolock().bit(true)) };
+            syskey::lock();
+        });
+    }
+
+    /// Unlock the PPS registers
+    ///
+    /// Returns a `Locked` error if the PPS registers are locked and IOL1WAY
+    /// is set in the configuration words.
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        if !self.is_locked() {
+            return Ok(());
+        }
+        if devcfg::devcfg3() & DEVCFG3_IOL1WAY != 0 {
+            return Err(Error::Locked);
+        }
+        critical_section::with(|_| {
+            unsafe { syskey::unlock() };
+            unsafe { (*CFG::ptr()).cfgcon.modify(|_, w| w.iolock().bit(false)) };
+            syskey::lock();
+        });
+        Ok(())
+    }
+
+    /// Map a pin to a virtual input function
+    ///
+    /// Returns the pin together with the error if the mapping is not possible.
+    pub fn map_input<MODE>(
+        &mut self,
+        pin: gpio::Pin<MODE>,
+        func: InputFunction,
+    ) -> Result<DynMappedInput<MODE>, (Error, gpio::Pin<MODE>)> {
+        let mask = 1 << func as u32;
+        if self.inputs_used & mask != 0 {
+            return Err((Error::FunctionInUse, pin));
+        }
+        if self.is_locked() {
+            return Err((Error::Locked, pin));
+        }
+        if !write_input_mapping(func, pin.id()) {
+            return Err((Error::InvalidMapping, pin));
+        }
+        self.inputs_used |= mask;
+        Ok(DynMappedInput { pin, func })
+    }
+
+    /// Unmap a pin from a virtual input function
+    ///
+    /// The input mapping register is set to a value that is reserved for the
+    /// device family so that no pin is selected. The virtual input function can
+    /// be mapped to another pin afterwards.
+    pub fn unmap_input<MODE>(
+        &mut self,
+        mapped: DynMappedInput<MODE>,
+    ) -> Result<gpio::Pin<MODE>, (Error, DynMappedInput<MODE>)> {
+        if self.is_locked() {
+            return Err((Error::Locked, mapped));
+        }
+        reset_input_mapping(mapped.func);
+        self.inputs_used &= !(1 << mapped.func as u32);
+        Ok(mapped.pin)
+    }
+
+    /// Map a pin to a virtual output function
+    ///
+    /// Returns the pin together with the error if the mapping is not possible.
+    pub fn map_output<MODE>(
+        &mut self,
+        pin: gpio::Pin<MODE>,
+        func: OutputFunction,
+    ) -> Result<DynMappedOutput<MODE>, (Error, gpio::Pin<MODE>)> {
+        if self.is_locked() {
+            return Err((Error::Locked, pin));
+        }
+        if !write_output_mapping(pin.id(), Some(func)) {
+            return Err((Error::InvalidMapping, pin));
+        }
+        Ok(DynMappedOutput { pin, func })
+    }
+
+    /// Unmap a pin from a virtual output function
+    ///
+    /// Returns the mapped pin together with the error if the PPS registers
+    /// are locked.
+    #[allow(clippy::type_complexity)]
+    pub fn unmap_output<MODE>(
+        &mut self,
+        mapped: DynMappedOutput<MODE>,
+    ) -> Result<gpio::Pin<MODE>, (Error, DynMappedOutput<MODE>)> {
+        if self.is_locked() {
+            return Err((Error::Locked, mapped));
+        }
+        write_output_mapping(mapped.pin.id(), None);
+        Ok(mapped.pin)
+    }
+}
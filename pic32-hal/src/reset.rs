@@ -0,0 +1,107 @@
+//! Reset cause detection and software reset
+//!
+//! The causes of a reset are indicated by flags in the RCON register. These
+//! flags are not cleared by a reset (except by a power-on reset). They should
+//! therefore be cleared after having evaluated them so that the cause of the
+//! next reset can be determined unambiguously.
+
+use crate::syskey;
+use enumflags2::{bitflags, BitFlags};
+use mips_mcu::interrupt;
+
+#[cfg(feature = "pic32mx2x4fxxxb")]
+use crate::pac::CRU as RCON;
+
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+use crate::pac::RCON;
+
+/// Reset causes and related status flags of the RCON register
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+#[bitflags]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u32)]
+pub enum ResetCause {
+    /// Power-on reset
+    PowerOn = 0x0001,
+    /// Brown-out reset
+    BrownOut = 0x0002,
+    /// Device was in Idle mode
+    Idle = 0x0004,
+    /// Device was in Sleep mode
+    Sleep = 0x0008,
+    /// Watchdog timer timeout
+    Watchdog = 0x0010,
+    /// Software reset
+    Software = 0x0040,
+    /// Master clear (MCLR pin) reset
+    Mclr = 0x0080,
+    /// Configuration word mismatch reset
+    ConfigMismatch = 0x0200,
+}
+
+/// Reset causes and related status flags of the RCON register
+#[cfg(feature = "pic32mx2x4fxxxb")]
+#[bitflags]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u32)]
+pub enum ResetCause {
+    /// Power-on reset
+    PowerOn = 0x0001,
+    /// Brown-out reset
+    BrownOut = 0x0002,
+    /// Device was in Idle mode
+    Idle = 0x0004,
+    /// Device was in Sleep mode
+    Sleep = 0x0008,
+    /// Watchdog timer timeout
+    Watchdog = 0x0010,
+    /// Software reset
+    Software = 0x0040,
+    /// Master clear (MCLR pin) reset
+    Mclr = 0x0080,
+    /// Configuration word mismatch reset
+    ConfigMismatch = 0x0200,
+    /// Wake-up from Deep Sleep mode
+    DeepSleep = 0x0400,
+}
+
+/// Read the reset cause flags
+pub fn reset_causes() -> BitFlags<ResetCause> {
+    let rcon = unsafe { (*RCON::ptr()).rcon.read().bits() };
+    BitFlags::from_bits_truncate(rcon)
+}
+
+/// Clear reset cause flags
+pub fn clear_reset_causes(causes: BitFlags<ResetCause>) {
+    unsafe { (*RCON::ptr()).rconclr.write(|w| w.bits(causes.bits())) };
+}
+
+/// Read and clear all reset cause flags
+pub fn take_reset_causes() -> BitFlags<ResetCause> {
+    let causes = reset_causes();
+    clear_reset_causes(causes);
+    causes
+}
+
+/// Perform a software reset
+pub fn software_reset() -> ! {
+    interrupt::disable();
+    let rcon = unsafe { &*RCON::ptr() };
+    unsafe { syskey::unlock() };
+    rcon.rswrstset.write(|w| w.swrst().bit(true));
+    // the reset is triggered by reading RSWRST
+    let _ = rcon.rswrst.read().bits();
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,338 @@
+//! Real-Time Clock and Calendar (RTCC)
+//!
+//! The RTCC is clocked by the secondary oscillator (SOSC), which is enabled
+//! when creating an `Rtcc` instance. Date and time are stored by the hardware
+//! in BCD format. This driver converts from and to `DateTime`, which can be
+//! converted from and to a Unix timestamp. The RTCC supports the years 2000 to
+//! 2099 only.
+//!
+//! The RTCC can generate an alarm at a specified time with a repeat mask that
+//! selects which parts of the date and time must match. The alarm can be
+//! repeated a specified number of times or indefinitely (chime). The RTCC
+//! interrupt can be controlled with the `int` module.
+
+use crate::pac::RTCC;
+use crate::syskey;
+
+#[cfg(feature = "pic32mx2x4fxxxb")]
+use crate::pac::CRU as OSC;
+
+#[cfg(any(
+    feature = "pic32mx1xxfxxxb",
+    feature = "pic32mx2xxfxxxb",
+    feature = "pic32mx37x",
+    feature = "pic32mx47x",
+))]
+use crate::pac::OSC;
+
+pub use pic32_datetime as datetime;
+
+pub use datetime::{DateTime, Weekday};
+
+// bit masks and positions of the RTCCON register
+const RTCCON_CAL_MASK: u32 = 0x03ff_0000;
+const RTCCON_CAL_POSITION: usize = 16;
+const RTCCON_ON_MASK: u32 = 0x8000;
+const RTCCON_RTSECSEL_MASK: u32 = 0x0080;
+const RTCCON_RTCCLKON_MASK: u32 = 0x0040;
+const RTCCON_RTCWREN_MASK: u32 = 0x0008;
+const RTCCON_RTCSYNC_MASK: u32 = 0x0004;
+const RTCCON_HALFSEC_MASK: u32 = 0x0002;
+const RTCCON_RTCOE_MASK: u32 = 0x0001;
+
+// bit masks and positions of the RTCALRM register
+const RTCALRM_ALRMEN_MASK: u32 = 0x8000;
+const RTCALRM_CHIME_MASK: u32 = 0x4000;
+const RTCALRM_ALRMSYNC_MASK: u32 = 0x1000;
+const RTCALRM_AMASK_POSITION: usize = 8;
+const RTCALRM_ARPT_MASK: u32 = 0x00ff;
+
+/// RTCC errors
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Error {
+    /// Value is out of range supported by the hardware
+    InvalidArgument,
+}
+
+/// Parts of the date and time that must match to trigger an alarm
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum AlarmMask {
+    /// Every half second
+    HalfSecond = 0,
+    /// Every second
+    Second = 1,
+    /// Every ten seconds (seconds digit matches)
+    TenSeconds = 2,
+    /// Every minute (seconds match)
+    Minute = 3,
+    /// Every ten minutes (minutes digit and seconds match)
+    TenMinutes = 4,
+    /// Every hour (minutes and seconds match)
+    Hour = 5,
+    /// Every day (time matches)
+    Day = 6,
+    /// Every week (weekday and time match)
+    Week = 7,
+    /// Every month (day and time match)
+    Month = 8,
+    /// Every year (month, day and time match)
+    Year = 9,
+}
+
+/// Repetition of the alarm
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum AlarmRepeat {
+    /// Alarm is triggered `n + 1` times
+    Times(u8),
+    /// Alarm is repeated indefinitely (chime)
+    Chime,
+}
+
+/// Signal output on the RTCC pin
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Output {
+    /// Alarm pulse
+    AlarmPulse,
+    /// Seconds clock
+    SecondsClock,
+}
+
+const fn to_bcd(value: u8) -> u32 {
+    (((value / 10) << 4) | (value % 10)) as u32
+}
+
+const fn from_bcd(bcd: u32) -> u8 {
+    (((bcd >> 4) & 0xf) * 10 + (bcd & 0xf)) as u8
+}
+
+/// Encode the time as in the RTCTIME and ALRMTIME registers
+fn encode_time(hour: u8, minute: u8, second: u8) -> u32 {
+    to_bcd(hour) << 24 | to_bcd(minute) << 16 | to_bcd(second) << 8
+}
+
+/// Encode the date as in the RTCDATE and ALRMDATE registers
+fn encode_date(year: u16, month: u8, day: u8, weekday: Weekday) -> u32 {
+    to_bcd((year % 100) as u8) << 24 | to_bcd(month) << 16 | to_bcd(day) << 8 | weekday as u32
+}
+
+/// Real-Time Clock and Calendar
+pub struct Rtcc {
+    rtcc: RTCC,
+}
+
+impl Rtcc {
+    /// Create a new `Rtcc` instance and enable the SOSC
+    ///
+    /// The date, time and the enable state of the RTCC are not changed so that
+    /// a running clock keeps its time across a reset.
+    pub fn new(rtcc: RTCC) -> Self {
+        let osc = unsafe { &*OSC::ptr() };
+        critical_section::with(|_| {
+            unsafe { syskey::unlock() };
+            osc.oscconset.write(|w| w.soscen().bit(true));
+            syskey::lock();
+        });
+        Rtcc { rtcc }
+    }
+
+    /// Return the RTCC peripheral consuming the `Rtcc` instance
+    ///
+    /// The RTCC remains in its current state.
+    pub fn free(self) -> RTCC {
+        self.rtcc
+    }
+
+    /// Enable or disable writing to the RTCC value registers and to the
+    /// calibration value (RTCWREN)
+    fn set_write_enable(&mut self, enable: bool) {
+        if enable {
+            critical_section::with(|_| {
+                unsafe { syskey::unlock() };
+                self.rtcc
+                    .rtcconset
+                    .write(|w| unsafe { w.bits(RTCCON_RTCWREN_MASK) });
+                syskey::lock();
+            });
+        } else {
+            self.rtcc
+                .rtcconclr
+                .write(|w| unsafe { w.bits(RTCCON_RTCWREN_MASK) });
+        }
+    }
+
+    /// Turn the RTCC on
+    pub fn enable(&mut self) {
+        self.set_write_enable(true);
+        self.rtcc
+            .rtcconset
+            .write(|w| unsafe { w.bits(RTCCON_ON_MASK) });
+        self.set_write_enable(false);
+    }
+
+    /// Turn the RTCC off and wait until the RTCC clock is stopped
+    pub fn disable(&mut self) {
+        self.set_write_enable(true);
+        self.rtcc
+            .rtcconclr
+            .write(|w| unsafe { w.bits(RTCCON_ON_MASK) });
+        self.set_write_enable(false);
+        while self.rtcc.rtccon.read().bits() & RTCCON_RTCCLKON_MASK != 0 {}
+    }
+
+    /// Check if the RTCC is on
+    pub fn is_enabled(&self) -> bool {
+        self.rtcc.rtccon.read().bits() & RTCCON_ON_MASK != 0
+    }
+
+    /// Set date and time
+    ///
+    /// The RTCC is turned on and the seconds divider is reset.
+    pub fn set_datetime(&mut self, datetime: &DateTime) {
+        let time = encode_time(datetime.hour(), datetime.minute(), datetime.second());
+        let date = encode_date(
+            datetime.year(),
+            datetime.month(),
+            datetime.day(),
+            datetime.weekday(),
+        );
+        self.disable();
+        self.set_write_enable(true);
+        self.rtcc.rtctime.write(|w| unsafe { w.bits(time) });
+        self.rtcc.rtcdate.write(|w| unsafe { w.bits(date) });
+        self.rtcc
+            .rtcconset
+            .write(|w| unsafe { w.bits(RTCCON_ON_MASK) });
+        self.set_write_enable(false);
+    }
+
+    /// Get date and time
+    ///
+    /// Returns `None` if the RTCC value registers do not contain a valid date
+    /// and time, e.g. because they have not been set after power-on.
+    pub fn datetime(&self) -> Option<DateTime> {
+        // read until no rollover occurred in between
+        let (time, date) = loop {
+            let time = self.rtcc.rtctime.read().bits();
+            let date = self.rtcc.rtcdate.read().bits();
+            if time == self.rtcc.rtctime.read().bits() {
+                break (time, date);
+            }
+        };
+        DateTime::new(
+            2000 + from_bcd(date >> 24) as u16,
+            from_bcd(date >> 16),
+            from_bcd(date >> 8),
+            from_bcd(time >> 24),
+            from_bcd(time >> 16),
+            from_bcd(time >> 8),
+        )
+    }
+
+    /// Check if the RTCC is in the second half of the current second
+    pub fn is_second_half(&self) -> bool {
+        self.rtcc.rtccon.read().bits() & RTCCON_HALFSEC_MASK != 0
+    }
+
+    /// Check if a rollover of the RTCC value registers can occur within the
+    /// next 32 RTCC clock cycles (RTCSYNC)
+    pub fn is_sync_pending(&self) -> bool {
+        self.rtcc.rtccon.read().bits() & RTCCON_RTCSYNC_MASK != 0
+    }
+
+    /// Set the calibration value
+    ///
+    /// Once every minute, `cal` RTCC clock pulses are added (positive value)
+    /// or subtracted (negative value) to compensate for a drift of the SOSC.
+    /// The allowed range is -512 to 511.
+    pub fn set_calibration(&mut self, cal: i16) -> Result<(), Error> {
+        if !(-512..=511).contains(&cal) {
+            return Err(Error::InvalidArgument);
+        }
+        let cal = ((cal as u32) << RTCCON_CAL_POSITION) & RTCCON_CAL_MASK;
+        self.set_write_enable(true);
+        // the CAL value can be written only while no rollover is imminent
+        while self.is_enabled() && self.is_sync_pending() {}
+        self.rtcc
+            .rtcconclr
+            .write(|w| unsafe { w.bits(RTCCON_CAL_MASK) });
+        self.rtcc.rtcconset.write(|w| unsafe { w.bits(cal) });
+        self.set_write_enable(false);
+        Ok(())
+    }
+
+    /// Get the calibration value
+    pub fn calibration(&self) -> i16 {
+        let cal = (self.rtcc.rtccon.read().bits() & RTCCON_CAL_MASK) >> RTCCON_CAL_POSITION;
+        // sign extension of the 10-bit value
+        ((cal as i16) << 6) >> 6
+    }
+
+    /// Enable the signal output on the RTCC pin
+    pub fn enable_output(&mut self, output: Output) {
+        match output {
+            Output::AlarmPulse => self
+                .rtcc
+                .rtcconclr
+                .write(|w| unsafe { w.bits(RTCCON_RTSECSEL_MASK) }),
+            Output::SecondsClock => self
+                .rtcc
+                .rtcconset
+                .write(|w| unsafe { w.bits(RTCCON_RTSECSEL_MASK) }),
+        }
+        self.rtcc
+            .rtcconset
+            .write(|w| unsafe { w.bits(RTCCON_RTCOE_MASK) });
+    }
+
+    /// Disable the signal output on the RTCC pin
+    pub fn disable_output(&mut self) {
+        self.rtcc
+            .rtcconclr
+            .write(|w| unsafe { w.bits(RTCCON_RTCOE_MASK) });
+    }
+
+    /// Set and enable the alarm
+    ///
+    /// The alarm is triggered when the parts of `datetime` selected by `mask`
+    /// match the current date and time. The year of `datetime` is ignored.
+    pub fn set_alarm(&mut self, datetime: &DateTime, mask: AlarmMask, repeat: AlarmRepeat) {
+        let time = encode_time(datetime.hour(), datetime.minute(), datetime.second());
+        // the alarm registers do not contain a year
+        let date = encode_date(0, datetime.month(), datetime.day(), datetime.weekday());
+        let (arpt, chime) = match repeat {
+            AlarmRepeat::Times(n) => (n as u32, 0),
+            AlarmRepeat::Chime => (RTCALRM_ARPT_MASK, RTCALRM_CHIME_MASK),
+        };
+        self.disable_alarm();
+        self.rtcc.alrmtime.write(|w| unsafe { w.bits(time) });
+        self.rtcc.alrmdate.write(|w| unsafe { w.bits(date) });
+        let rtcalrm = (mask as u32) << RTCALRM_AMASK_POSITION | chime | arpt;
+        self.rtcc.rtcalrm.write(|w| unsafe { w.bits(rtcalrm) });
+        self.rtcc
+            .rtcalrmset
+            .write(|w| unsafe { w.bits(RTCALRM_ALRMEN_MASK) });
+    }
+
+    /// Disable the alarm
+    pub fn disable_alarm(&mut self) {
+        self.rtcc
+            .rtcalrmclr
+            .write(|w| unsafe { w.bits(RTCALRM_ALRMEN_MASK) });
+        // the alarm registers can be written only when ALRMSYNC is cleared
+        while self.rtcc.rtcalrm.read().bits() & RTCALRM_ALRMSYNC_MASK != 0 {}
+    }
+
+    /// Check if the alarm is enabled
+    ///
+    /// The alarm is disabled by the hardware after the last repetition unless
+    /// chime is enabled.
+    pub fn is_alarm_enabled(&self) -> bool {
+        self.rtcc.rtcalrm.read().bits() & RTCALRM_ALRMEN_MASK != 0
+    }
+
+    /// Number of remaining alarm repetitions
+    pub fn alarm_repetitions(&self) -> u8 {
+        (self.rtcc.rtcalrm.read().bits() & RTCALRM_ARPT_MASK) as u8
+    }
+}
@@ -1,5 +1,6 @@
 //! SPI driver (SPI master)
 
+use crate::int::{Interrupt, InterruptSource, PeripheralInterrupt};
 use crate::pac::{SPI1, SPI2};
 use core::{cmp::max, slice};
 use embedded_hal::spi::{ErrorKind, ErrorType, SpiBus};
@@ -8,6 +9,19 @@ pub use embedded_hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_
 /// SPI error
 pub type Error = ErrorKind;
 
+/// SPI interrupt events
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Event {
+    /// Receive overflow error
+    Error,
+
+    /// Data received
+    Rx,
+
+    /// Space available in the transmit buffer
+    Tx,
+}
+
 pub enum Proto {
     Spi(Mode),
     AudioI2s(AudioFrameFormat),
@@ -51,7 +65,21 @@ pub struct Spi<SPI> {
 }
 
 macro_rules! spi {
-    ($Id:ident, $Spi:ident) => {
+    ($Id:ident, $Spi:ident, $Vector:ident, $Err:ident, $RxIrq:ident, $TxIrq:ident) => {
+        impl PeripheralInterrupt for Spi<$Spi> {
+            type Event = Event;
+
+            const VECTOR: Interrupt = Interrupt::$Vector;
+
+            fn interrupt_source(event: Event) -> InterruptSource {
+                match event {
+                    Event::Error => InterruptSource::$Err,
+                    Event::Rx => InterruptSource::$RxIrq,
+                    Event::Tx => InterruptSource::$TxIrq,
+                }
+            }
+        }
+
         impl Spi<$Spi> {
             /// create an SPI instance
             ///
@@ -123,6 +151,15 @@ macro_rules! spi {
                 Spi { spi }
             }
 
+            /// Stop or continue operation in Idle mode
+            pub fn set_stop_in_idle(&mut self, stop: bool) {
+                if stop {
+                    self.spi.con1set.write(|w| w.sidl().bit(true));
+                } else {
+                    self.spi.con1clr.write(|w| w.sidl().bit(true));
+                }
+            }
+
             pub fn free(self) -> $Spi {
                 self.spi.con1.write(|w| w.on().bit(false)); // turn SPI off
                 self.spi
@@ -215,5 +252,5 @@ macro_rules! spi {
     };
 }
 
-spi!(spi1, SPI1);
-spi!(spi2, SPI2);
+spi!(spi1, SPI1, SPI_1, SPI1_ERR, SPI1_RX, SPI1_TX);
+spi!(spi2, SPI2, SPI_2, SPI2_ERR, SPI2_RX, SPI2_TX);
@@ -0,0 +1,24 @@
+//! System unlock sequence (SYSKEY)
+//!
+//! Write access to some registers (e.g. OSCCON or RSWRST) requires the system
+//! unlock sequence to be carried out beforehand.
+
+use crate::pac::CFG;
+
+/// Perform the system unlock sequence
+///
+/// # Safety
+///
+/// Must be executed without being interrupted, e.g. in a critical section,
+/// and should be followed by [`lock`] as soon as possible.
+pub unsafe fn unlock() {
+    let cfg = &*CFG::ptr();
+    cfg.syskey.write(|w| w.bits(0));
+    cfg.syskey.write(|w| w.bits(0xaa99_6655));
+    cfg.syskey.write(|w| w.bits(0x5566_99aa));
+}
+
+/// Re-lock the system
+pub fn lock() {
+    unsafe { (*CFG::ptr()).syskey.write(|w| w.bits(0)) };
+}
@@ -1,6 +1,7 @@
 //! Timer type A
 
 use super::Clocking;
+use crate::int::{Interrupt, InterruptSource, Irq, PeripheralInterrupt};
 use crate::pac::TMR1;
 use core::marker::PhantomData;
 
@@ -85,6 +86,15 @@ impl<MODE> Timer<MODE> {
         Timer { timer, _marker: PhantomData }
     }
 
+    /// Stop or continue operation in Idle mode
+    pub fn set_stop_in_idle(&mut self, stop: bool) {
+        if stop {
+            self.timer.contset.write(|w| w.sidl().set_bit());
+        } else {
+            self.timer.contclr.write(|w| w.sidl().set_bit());
+        }
+    }
+
     /// Turn the timer off
     pub fn free(self) -> TMR1 {
         self.timer.contclr.write(|w| w.on().set_bit());
@@ -107,6 +117,16 @@ impl<MODE> Timer<MODE> {
     }
 }
 
+impl<MODE> PeripheralInterrupt for Timer<MODE> {
+    type Event = Irq;
+
+    const VECTOR: Interrupt = Interrupt::TIMER_1;
+
+    fn interrupt_source(_event: Irq) -> InterruptSource {
+        InterruptSource::TIMER_1
+    }
+}
+
 impl Timer<TimerSynchronous> {
     /// Set the current timer count value
     pub fn set_tmr(&mut self, tmr: u16) {
@@ -1,6 +1,7 @@
 //! Timer type B
 
 use super::Clocking;
+use crate::int::{Interrupt, InterruptSource, Irq, PeripheralInterrupt};
 use crate::pac::{TMR2, TMR3, TMR4, TMR5};
 
 /// Clock pre scaler configuration for timer type B
@@ -38,7 +39,17 @@ pub struct Timer<TIMER> {
 }
 
 macro_rules! timerb_impl {
-    ($constructor: ident, $timer: ty) => {
+    ($constructor: ident, $timer: ty, $irq: ident) => {
+        impl PeripheralInterrupt for Timer<$timer> {
+            type Event = Irq;
+
+            const VECTOR: Interrupt = Interrupt::$irq;
+
+            fn interrupt_source(_event: Irq) -> InterruptSource {
+                InterruptSource::$irq
+            }
+        }
+
         impl Timer<$timer> {
             /// Initialize the timer
             pub fn $constructor(
@@ -61,6 +72,15 @@ macro_rules! timerb_impl {
                 Self { timer }
             }
 
+            /// Stop or continue operation in Idle mode
+            pub fn set_stop_in_idle(&mut self, stop: bool) {
+                if stop {
+                    self.timer.contset.write(|w| w.sidl().set_bit());
+                } else {
+                    self.timer.contclr.write(|w| w.sidl().set_bit());
+                }
+            }
+
             /// Turn the timer off
             pub fn free(self) -> $timer {
                 self.timer.contclr.write(|w| w.on().set_bit());
@@ -90,10 +110,10 @@ macro_rules! timerb_impl {
     };
 }
 
-timerb_impl!(timer2, TMR2);
-timerb_impl!(timer3, TMR3);
-timerb_impl!(timer4, TMR4);
-timerb_impl!(timer5, TMR5);
+timerb_impl!(timer2, TMR2, TIMER_2);
+timerb_impl!(timer3, TMR3, TIMER_3);
+timerb_impl!(timer4, TMR4, TIMER_4);
+timerb_impl!(timer5, TMR5, TIMER_5);
 
 /// HAL struct for a pair of timers of type B (32-bit mode)
 pub struct Timer32<TIMERL, TIMERH> {
@@ -102,7 +122,18 @@ pub struct Timer32<TIMERL, TIMERH> {
 }
 
 macro_rules! timer32_impl {
-    ($constructor: ident, $timer_low: ty, $timer_high: ty) => {
+    ($constructor: ident, $timer_low: ty, $timer_high: ty, $irq: ident) => {
+        // the interrupt is generated by the high timer
+        impl PeripheralInterrupt for Timer32<$timer_low, $timer_high> {
+            type Event = Irq;
+
+            const VECTOR: Interrupt = Interrupt::$irq;
+
+            fn interrupt_source(_event: Irq) -> InterruptSource {
+                InterruptSource::$irq
+            }
+        }
+
         impl Timer32<$timer_low, $timer_high> {
             /// Initialize the timer
             pub fn $constructor(
@@ -129,6 +160,17 @@ macro_rules! timer32_impl {
                 Self { timer_low, timer_high }
             }
 
+            /// Stop or continue operation in Idle mode
+            pub fn set_stop_in_idle(&mut self, stop: bool) {
+                if stop {
+                    self.timer_low.contset.write(|w| w.sidl().set_bit());
+                    self.timer_high.contset.write(|w| w.sidl().set_bit());
+                } else {
+                    self.timer_low.contclr.write(|w| w.sidl().set_bit());
+                    self.timer_high.contclr.write(|w| w.sidl().set_bit());
+                }
+            }
+
             /// Turn the timer off
             pub fn free(self) -> ($timer_low, $timer_high) {
                 self.timer_low.contclr.write(|w| w.on().set_bit());
@@ -159,5 +201,5 @@ macro_rules! timer32_impl {
     };
 }
 
-timer32_impl!(timer2_3, TMR2, TMR3);
-timer32_impl!(timer4_5, TMR4, TMR5);
+timer32_impl!(timer2_3, TMR2, TMR3, TIMER_3);
+timer32_impl!(timer4_5, TMR4, TMR5, TIMER_5);
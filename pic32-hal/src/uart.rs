@@ -5,6 +5,7 @@ use core::fmt;
 use core::marker::PhantomData;
 
 use crate::clock::Osc;
+use crate::int::{Interrupt, InterruptSource, PeripheralInterrupt};
 use crate::pac::{UART1, UART2};
 use crate::pps::{input, output, IsConnected, MappedPin};
 
@@ -45,6 +46,19 @@ pub enum ReadError {
     Break,
 }
 
+/// UART interrupt events
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Event {
+    /// Receive error
+    Error,
+
+    /// Data received
+    Rx,
+
+    /// Space available in the TX FIFO
+    Tx,
+}
+
 /// UART configuration
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Config {
@@ -104,8 +118,30 @@ impl embedded_io::Error for ReadError {
     }
 }
 
+macro_rules! uart_interrupt_impl {
+    ([$($G:ident),*] $Type:ty, $Vector:ident, $Err:ident, $RxIrq:ident, $TxIrq:ident) => {
+        impl<$($G),*> PeripheralInterrupt for $Type {
+            type Event = Event;
+
+            const VECTOR: Interrupt = Interrupt::$Vector;
+
+            fn interrupt_source(event: Event) -> InterruptSource {
+                match event {
+                    Event::Error => InterruptSource::$Err,
+                    Event::Rx => InterruptSource::$RxIrq,
+                    Event::Tx => InterruptSource::$TxIrq,
+                }
+            }
+        }
+    };
+}
+
 macro_rules! uart_impl {
-    ($Id:ident, $Uart:ident, $Rx:ty, $Tx:ty) => {
+    ($Id:ident, $Uart:ident, $Rx:ty, $Tx:ty, $Vector:ident, $Err:ident, $RxIrq:ident, $TxIrq:ident) => {
+        uart_interrupt_impl!([RX, TX] Uart<$Uart, RX, TX>, $Vector, $Err, $RxIrq, $TxIrq);
+        uart_interrupt_impl!([] Rx<$Uart>, $Vector, $Err, $RxIrq, $TxIrq);
+        uart_interrupt_impl!([] Tx<$Uart>, $Vector, $Err, $RxIrq, $TxIrq);
+
         impl<RX, TX> Uart<$Uart, MappedPin<RX, $Rx>, MappedPin<TX, $Tx>> {
             pub fn $Id(
                 uart: $Uart,
@@ -151,6 +187,15 @@ macro_rules! uart_impl {
                 tx.transmit_break();
             }
 
+            /// Stop or continue operation in Idle mode
+            pub fn set_stop_in_idle(&mut self, stop: bool) {
+                if stop {
+                    self.uart.modeset.write(|w| w.sidl().bit(true));
+                } else {
+                    self.uart.modeclr.write(|w| w.sidl().bit(true));
+                }
+            }
+
             pub fn free(self) -> ($Uart, MappedPin<RX, $Rx>, MappedPin<TX, $Tx>) {
                 unsafe { (*$Uart::ptr()).modeclr.write(|w| w.on().bit(true)) };
                 (self.uart, self.rx, self.tx)
@@ -372,8 +417,26 @@ macro_rules! uart_impl {
     };
 }
 
-uart_impl!(uart1, UART1, input::U1rx, output::U1tx);
-uart_impl!(uart2, UART2, input::U2rx, output::U2tx);
+uart_impl!(
+    uart1,
+    UART1,
+    input::U1rx,
+    output::U1tx,
+    UART_1,
+    UART1_ERR,
+    UART1_RX,
+    UART1_TX
+);
+uart_impl!(
+    uart2,
+    UART2,
+    input::U2rx,
+    output::U2tx,
+    UART_2,
+    UART2_ERR,
+    UART2_RX,
+    UART2_TX
+);
 
 impl<UART> fmt::Write for Tx<UART>
 where
@@ -1,14 +1,42 @@
 //! USB function driver for PIC32 devices with Full Speed USB hardware
 //!
-//! Uses dynamic memory (```alloc``` crate) to allocate USB buffers and buffer
-//! descriptor table
+//! The buffer descriptor table and the USB buffers are either taken from a
+//! statically allocated `UsbMemory` (see `UsbBus::with_memory()`) or allocated
+//! from the heap by means of the ```alloc``` crate (see `UsbBus::new()`).
 //!
-//! This module can be enabled with the ```usb-device``` feature.
+//! This module can be enabled with the ```usb-device``` feature. The heap based
+//! allocation requires the ```usb-alloc``` feature, which is enabled by
+//! default. Without it, the ```alloc``` crate is not used.
+//!
+//! Isochronous endpoints transfer at most one packet per frame without
+//! handshake and data toggle synchronization. Each endpoint has two buffers
+//! (ping-pong buffering) so that the packet for the next frame can be written
+//! (IN) or the packet of the previous frame can be read (OUT) while the
+//! current packet is transferred. Packets of an isochronous OUT endpoint are
+//! lost if both buffers are full. The IN and OUT endpoints sharing an endpoint
+//! number must both be isochronous or both be non-isochronous because they
+//! share the handshake setting.
+//!
+//! The `host` submodule contains a driver for the USB host role. The `otg`
+//! submodule switches between the device and the host role depending on the
+//! ID pin.
+//!
+//! When the host suspends the bus, `UsbDevice::poll()` puts the USB module into
+//! suspend mode. A bus-powered device should then enter Sleep mode by calling
+//! `UsbBus::sleep_while_suspended()` to meet the suspend current limit. Bus
+//! activity wakes up the CPU if the USB interrupt is enabled in the interrupt
+//! controller.
 
+#[cfg(feature = "usb-alloc")]
 extern crate alloc;
+#[cfg(feature = "usb-alloc")]
 use alloc::alloc::{alloc, dealloc, Layout};
+#[cfg(feature = "usb-alloc")]
 use alloc::boxed::Box;
 use core::cell::RefCell;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "usb-alloc")]
 use core::pin::Pin;
 use core::ptr::{read_volatile, write_volatile};
 use core::slice;
@@ -18,6 +46,9 @@ use mips_mcu::fmt::virt_to_phys;
 use mips_mcu::PhysicalAddress;
 
 use crate::pac::USB;
+use crate::power;
+
+use embedded_hal::delay::DelayNs;
 
 use usb_device as udev;
 use usb_device::bus::PollResult;
@@ -25,6 +56,9 @@ use usb_device::bus::UsbBusAllocator;
 use usb_device::endpoint::{EndpointAddress, EndpointType};
 use usb_device::{Result, UsbDirection, UsbError};
 
+pub mod host;
+pub mod otg;
+
 // bit masks for endpoint control registers
 const EPREG_EPHSHK_MASK: u8 =   0x01;
 const EPREG_EPSTALL_MASK: u8 =  0x02;
@@ -81,6 +115,10 @@ impl BufferDescriptor {
         unsafe { write_volatile(&mut self.flags, flags) };
     }
 
+    fn byte_count(&self) -> u16 {
+        unsafe { read_volatile(&self.byte_count) }
+    }
+
     fn set_byte_count(&mut self, byte_count: u16) {
         unsafe { write_volatile(&mut self.byte_count, byte_count) };
     }
@@ -97,6 +135,17 @@ const USB_PID_SETUP: u8 = 0xd;
 
 const N_ENDPOINTS: usize = 16;
 
+/// Maximum packet size of isochronous endpoints (limited by the buffer
+/// descriptor byte count)
+const MAX_ISO_PACKET_SIZE: u16 = 1023;
+
+/// Maximum packet size of control, bulk and interrupt endpoints
+const MAX_PACKET_SIZE: u16 = 64;
+
+fn is_iso(ep_type: EndpointType) -> bool {
+    matches!(ep_type, EndpointType::Isochronous { synchronization: _, usage: _ })
+}
+
 #[repr(C, align(512))]
 union BufferDescriptorTable {
     flat: [BufferDescriptor; 4 * N_ENDPOINTS],
@@ -117,6 +166,143 @@ impl BufferDescriptorTable {
     }
 }
 
+/// Statically allocated memory for the buffer descriptor table and `N` bytes
+/// of USB buffers
+///
+/// Each endpoint requires two buffers (even and odd) of the maximum packet
+/// size of the endpoint. For example, a control endpoint with a maximum packet
+/// size of 64 and two bulk endpoints with a maximum packet size of 64 require
+/// `N = 2 * (2 * 64) + 2 * 2 * 64 = 512` bytes (the control endpoint has an
+/// IN and an OUT direction). Typical use:
+///
+/// ```ignore
+/// use core::ptr::addr_of_mut;
+///
+/// static mut USB_MEMORY: UsbMemory<512> = UsbMemory::new();
+///
+/// let usb_bus = UsbBus::with_memory(p.USB, unsafe { &mut *addr_of_mut!(USB_MEMORY) });
+/// ```
+#[repr(C, align(512))]
+pub struct UsbMemory<const N: usize> {
+    bdt: BufferDescriptorTable,
+    buffers: [u8; N],
+}
+
+impl<const N: usize> UsbMemory<N> {
+    /// Create a new `UsbMemory`
+    pub const fn new() -> Self {
+        UsbMemory {
+            bdt: BufferDescriptorTable::new(),
+            buffers: [0; N],
+        }
+    }
+}
+
+impl<const N: usize> UsbMemory<N> {
+    /// Split into the buffer descriptor table and the USB buffers
+    fn parts(&'static mut self) -> MemoryParts {
+        MemoryParts {
+            bdt: &mut self.bdt,
+            buffers: &mut self.buffers,
+        }
+    }
+}
+
+impl<const N: usize> Default for UsbMemory<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Buffer descriptor table and USB buffers of a `UsbMemory`
+struct MemoryParts {
+    bdt: &'static mut BufferDescriptorTable,
+    buffers: &'static mut [u8],
+}
+
+/// Storage of the buffer descriptor table
+enum Bdt {
+    #[cfg(feature = "usb-alloc")]
+    Heap(Pin<Box<BufferDescriptorTable>>),
+    Static(&'static mut BufferDescriptorTable),
+    /// Handed back to `Otg` while the `UsbBus` is dropped
+    Released,
+}
+
+impl Deref for Bdt {
+    type Target = BufferDescriptorTable;
+
+    fn deref(&self) -> &BufferDescriptorTable {
+        match self {
+            #[cfg(feature = "usb-alloc")]
+            Bdt::Heap(bdt) => bdt,
+            Bdt::Static(bdt) => bdt,
+            Bdt::Released => unreachable!(),
+        }
+    }
+}
+
+impl DerefMut for Bdt {
+    fn deref_mut(&mut self) -> &mut BufferDescriptorTable {
+        match self {
+            #[cfg(feature = "usb-alloc")]
+            Bdt::Heap(bdt) => bdt,
+            Bdt::Static(bdt) => bdt,
+            Bdt::Released => unreachable!(),
+        }
+    }
+}
+
+/// Source of the USB buffers
+enum BufferPool {
+    /// Allocate from the heap
+    #[cfg(feature = "usb-alloc")]
+    Heap,
+    /// Take from the unused part of a `UsbMemory`
+    Static {
+        buffers: &'static mut [u8],
+        used: usize,
+    },
+}
+
+impl BufferPool {
+    /// Allocate a buffer of `size` bytes
+    ///
+    /// Static buffers are never returned to the pool because endpoints are
+    /// allocated only once.
+    fn alloc(&mut self, size: usize) -> Result<*mut u8> {
+        match self {
+            #[cfg(feature = "usb-alloc")]
+            BufferPool::Heap => {
+                let b = unsafe {
+                    alloc(Layout::from_size_align(size, 1).map_err(|_| UsbError::EndpointOverflow)?)
+                };
+                if b.is_null() {
+                    return Err(UsbError::EndpointOverflow);
+                }
+                Ok(b)
+            }
+            BufferPool::Static { buffers, used } => {
+                if size > buffers.len() - *used {
+                    return Err(UsbError::EndpointMemoryOverflow);
+                }
+                let buf = buffers[*used..].as_mut_ptr();
+                *used += size;
+                Ok(buf)
+            }
+        }
+    }
+
+    #[cfg(feature = "usb-alloc")]
+    fn is_heap(&self) -> bool {
+        match self {
+            #[cfg(feature = "usb-alloc")]
+            BufferPool::Heap => true,
+            BufferPool::Static { .. } => false,
+        }
+    }
+}
+
 struct EndpointControlBlock {
     next_odd: bool,         // next BD to use is odd BD
     data01: bool,           // data toggle flag for next transaction
@@ -127,6 +313,8 @@ struct EndpointControlBlock {
     ep_type: EndpointType,
     ep_size: u16,           // max. transaction size (== size of ep_buf[.])
     ep_buf: [*mut u8; 2],   // transaction buffers
+    #[cfg(feature = "usb-alloc")]
+    heap_buf: bool,         // ep_buf allocated from the heap
     bd: *mut [BufferDescriptor; 2],
 }
 
@@ -138,25 +326,10 @@ impl EndpointControlBlock {
         ep_type: EndpointType,
         ep_addr: EndpointAddress,
         bd: *mut [BufferDescriptor; 2],
+        pool: &mut BufferPool,
     ) -> Result<EndpointControlBlock> {
-        let b0 = unsafe {
-            alloc(
-                Layout::from_size_align(ep_size as usize, 1)
-                    .map_err(|_| UsbError::EndpointOverflow)?,
-            )
-        };
-        if b0.is_null() {
-            return Err(UsbError::EndpointOverflow);
-        }
-        let b1 = unsafe {
-            alloc(
-                Layout::from_size_align(ep_size as usize, 1)
-                    .map_err(|_| UsbError::EndpointOverflow)?,
-            )
-        };
-        if b1.is_null() {
-            return Err(UsbError::EndpointOverflow);
-        }
+        let b0 = pool.alloc(ep_size as usize)?;
+        let b1 = pool.alloc(ep_size as usize)?;
         let bd_pair: &mut [BufferDescriptor; 2] = unsafe { &mut *bd };
         bd_pair[0].set_flags(0);
         bd_pair[0].set_buffer_address(virt_to_phys(b0));
@@ -187,6 +360,8 @@ impl EndpointControlBlock {
             ep_type,
             ep_size,
             ep_buf: [b0, b1],
+            #[cfg(feature = "usb-alloc")]
+            heap_buf: pool.is_heap(),
             bd,
         })
     }
@@ -225,7 +400,8 @@ impl EndpointControlBlock {
         }
         bd.set_buffer_address(virt_to_phys(self.ep_buf[self.next_odd as usize]));
         bd.set_byte_count(len as u16);
-        let is_iso_ep = matches!(self.ep_type, EndpointType::Isochronous { synchronization: _, usage: _ });
+        // isochronous transactions always use DATA0 and no data toggle synchronization
+        let is_iso_ep = is_iso(self.ep_type);
         bd.set_flags( BD_UOWN | 
                       if self.data01 { BD_DATA01 } else { 0 } |
                       if is_iso_ep { 0 } else { BD_DTS } |
@@ -299,8 +475,12 @@ impl EndpointControlBlock {
     }
 }
 
+#[cfg(feature = "usb-alloc")]
 impl Drop for EndpointControlBlock {
     fn drop(&mut self) {
+        if !self.heap_buf {
+            return;
+        }
         unsafe {
             dealloc(
                 self.ep_buf[0],
@@ -317,11 +497,16 @@ impl Drop for EndpointControlBlock {
 type Ecb = [[Option<EndpointControlBlock>; 2]; N_ENDPOINTS];
 
 struct UsbInner {
-    bdt: Pin<Box<BufferDescriptorTable>>,
-    usb: USB,
+    bdt: Bdt,
+    pool: BufferPool,
+    /// moved out when handing the peripheral back to `Otg`
+    usb: ManuallyDrop<USB>,
+    /// hand the USB peripheral and the memory back to `Otg` when dropped
+    otg: bool,
     ecb: Ecb,
     pr_out: u16,
     pr_su: u16,
+    suspended: bool,
 }
 
 /// Usb bus driver to be used with the usb-device crate.
@@ -329,7 +514,33 @@ pub struct UsbBus(Mutex<RefCell<UsbInner>>);
 
 impl UsbBus {
     /// Create a new UsbBus. Uses the heap for allocating the various buffers.
+    #[cfg(feature = "usb-alloc")]
     pub fn new(usb: USB) -> UsbBusAllocator<Self> {
+        Self::init(usb, Bdt::Heap(Box::pin(BufferDescriptorTable::new())), BufferPool::Heap, false)
+    }
+
+    /// Create a new UsbBus using a statically allocated `UsbMemory` for the
+    /// buffer descriptor table and the USB buffers. Does not use the heap.
+    ///
+    /// Allocating an endpoint fails with `UsbError::EndpointMemoryOverflow`
+    /// if the `UsbMemory` is too small.
+    pub fn with_memory<const N: usize>(
+        usb: USB,
+        memory: &'static mut UsbMemory<N>,
+    ) -> UsbBusAllocator<Self> {
+        Self::with_parts(usb, memory.parts(), false)
+    }
+
+    /// Create a new UsbBus using the memory parts lent by `Otg`
+    fn with_parts(usb: USB, parts: MemoryParts, otg: bool) -> UsbBusAllocator<Self> {
+        let pool = BufferPool::Static {
+            buffers: parts.buffers,
+            used: 0,
+        };
+        Self::init(usb, Bdt::Static(parts.bdt), pool, otg)
+    }
+
+    fn init(usb: USB, mut bdt: Bdt, pool: BufferPool, otg: bool) -> UsbBusAllocator<Self> {
         usb.u1con.write(unsafe { |w| w.bits(0) }); // first turn USB off
         usb.u1ie.write(unsafe { |w| w.bits(0) });
         //turn off VUSB, disable special USB OTG functions
@@ -341,8 +552,7 @@ impl UsbBus {
             unsafe { Self::write_epreg(i, 0) };
         }
 
-        // create Buffer Descriptor Table (BDT) and inform the hardware about it
-        let mut bdt = Box::pin(BufferDescriptorTable::new());
+        // inform the hardware about the Buffer Descriptor Table (BDT)
         let dma_addr = virt_to_phys(bdt.as_raw()).address() as u32;
         usb.u1bdtp3.write(unsafe { |w| w.bits(dma_addr >> 24) });
         usb.u1bdtp2.write(unsafe { |w| w.bits(dma_addr >> 16) });
@@ -350,10 +560,13 @@ impl UsbBus {
 
         let bus = UsbBus(Mutex::new(RefCell::new(UsbInner {
             bdt,
-            usb,
+            pool,
+            usb: ManuallyDrop::new(usb),
+            otg,
             ecb: Ecb::default(),
             pr_out: 0,
             pr_su: 0,
+            suspended: false,
         })));
         UsbBusAllocator::new(bus)
     }
@@ -374,6 +587,64 @@ impl UsbBus {
         });
     }
 
+    /// Get the 11-bit frame number of the last received SOF packet.
+    pub fn frame_number(&self) -> u16 {
+        critical_section::with(|cs| {
+            let inner = self.0.borrow_ref(cs);
+            let low = inner.usb.u1frml.read().bits() as u16 & 0xff;
+            let high = inner.usb.u1frmh.read().bits() as u16 & 0x07;
+            high << 8 | low
+        })
+    }
+
+    /// Check if the USB module is in suspend mode
+    pub fn is_suspended(&self) -> bool {
+        critical_section::with(|cs| self.0.borrow_ref(cs).suspended)
+    }
+
+    /// Enter Sleep mode if the USB module is in suspend mode
+    ///
+    /// The CPU wakes up when bus activity is detected, which requires the USB
+    /// interrupt to be enabled in the interrupt controller. `UsbDevice::poll()`
+    /// must be called afterwards to resume the USB module. Sleep mode is not
+    /// entered if bus activity is pending. Returns `true` if Sleep mode was
+    /// entered.
+    pub fn sleep_while_suspended(&self) -> bool {
+        critical_section::with(|cs| {
+            let inner = self.0.borrow_ref(cs);
+            if !inner.suspended {
+                return false;
+            }
+            // the sleep guard prevents Sleep mode entry if bus activity is
+            // pending
+            inner.usb.u1pwrcset.write(|w| w.uslpgrd().bit(true));
+            let sleep = !inner.usb.u1pwrc.read().uactpnd().bit();
+            if sleep {
+                power::sleep();
+            }
+            inner.usb.u1pwrcclr.write(|w| w.uslpgrd().bit(true));
+            sleep
+        })
+    }
+
+    /// Signal remote wakeup to the host
+    ///
+    /// Drives resume signaling for 10 ms. May only be used while the bus is
+    /// suspended and if the host enabled remote wakeup (see
+    /// `UsbDevice::remote_wakeup_enabled()`).
+    pub fn remote_wakeup(&self, delay: &mut impl DelayNs) {
+        critical_section::with(|cs| {
+            let inner = self.0.borrow_ref(cs);
+            inner.usb.u1pwrcclr.write(|w| w.ususpend().bit(true));
+            inner.usb.u1conset.write(|w| w.resume().bit(true));
+        });
+        delay.delay_ms(10);
+        critical_section::with(|cs| {
+            let inner = self.0.borrow_ref(cs);
+            inner.usb.u1conclr.write(|w| w.resume().bit(true));
+        });
+    }
+
     /// write to endpoint control register
     unsafe fn write_epreg(ndx: usize, val: u8) {
         let epregs = &((*USB::ptr()).u1ep0) as *const _ as usize;
@@ -391,11 +662,23 @@ impl UsbBus {
 
 impl Drop for UsbBus {
     fn drop(&mut self) {
-        critical_section::with(|cs| {
-            let usb = &self.0.borrow_ref(cs).usb;
-            usb.u1ie.write(unsafe { |w| w.bits(0) });
-            usb.u1pwrc.write(unsafe { |w| w.bits(0) });
-        });
+        let inner = self.0.get_mut().get_mut();
+        inner.usb.u1ie.write(unsafe { |w| w.bits(0) });
+        inner.usb.u1pwrc.write(unsafe { |w| w.bits(0) });
+        if inner.otg {
+            let bdt = match core::mem::replace(&mut inner.bdt, Bdt::Released) {
+                Bdt::Static(bdt) => bdt,
+                _ => unreachable!(),
+            };
+            let buffers = match &mut inner.pool {
+                BufferPool::Static { buffers, .. } => core::mem::take(buffers),
+                #[cfg(feature = "usb-alloc")]
+                BufferPool::Heap => unreachable!(),
+            };
+            // inner.usb is not accessed after being moved out
+            let usb = unsafe { ManuallyDrop::take(&mut inner.usb) };
+            otg::release_device(usb, MemoryParts { bdt, buffers });
+        }
     }
 }
 
@@ -411,8 +694,19 @@ impl usb_device::bus::UsbBus for UsbBus {
         ep_size: u16,
         _interval: u8,
     ) -> Result<EndpointAddress> {
+        let max_size = if is_iso(ep_type) { MAX_ISO_PACKET_SIZE } else { MAX_PACKET_SIZE };
+        if ep_size > max_size {
+            return Err(UsbError::Unsupported);
+        }
         critical_section::with(|cs| {
             let mut inner = self.0.borrow_ref_mut(cs);
+            // the endpoint in the other direction must not conflict with
+            // respect to the handshake setting
+            let compatible = |inner: &UsbInner, ep: usize, dir: usize| {
+                inner.ecb[ep][dir ^ 1]
+                    .as_ref()
+                    .is_none_or(|other| is_iso(other.ep_type) == is_iso(ep_type))
+            };
             let addr = if let Some(a) = ep_addr {
                 // consistency check for ep_dir
                 if a.direction() != ep_dir {
@@ -425,7 +719,7 @@ impl usb_device::bus::UsbBus for UsbBus {
                 }
                 // check if endpoint is already in use
                 let dir = (a.direction() as u8 >> 7) as usize;
-                if inner.ecb[ep][dir].is_some() {
+                if inner.ecb[ep][dir].is_some() || !compatible(&inner, ep, dir) {
                     return Err(UsbError::InvalidEndpoint);
                 }
                 a
@@ -434,7 +728,7 @@ impl usb_device::bus::UsbBus for UsbBus {
                 let dir = (ep_dir as u8 >> 7) as usize;
                 let mut addr = None;
                 for ep in 1..N_ENDPOINTS {
-                    if inner.ecb[ep][dir].is_none() {
+                    if inner.ecb[ep][dir].is_none() && compatible(&inner, ep, dir) {
                         addr = Some(EndpointAddress::from_parts(ep, ep_dir));
                         break;
                     }
@@ -448,8 +742,9 @@ impl usb_device::bus::UsbBus for UsbBus {
             let dir = addr.direction() as usize >> 7;
 
             // initialize buffer descriptors and endpoint control block
+            let inner = &mut *inner;
             let bd_pair = unsafe { &mut inner.bdt.ep_dir_ppbi[ep][dir] };
-            let ecb = EndpointControlBlock::alloc(ep_size, ep_type, addr, bd_pair)?;
+            let ecb = EndpointControlBlock::alloc(ep_size, ep_type, addr, bd_pair, &mut inner.pool)?;
             inner.ecb[ep][dir] = Some(ecb);
             //if ep_type == EndpointType::Control && addr.is_out() {
             if addr.is_out() {
@@ -469,7 +764,9 @@ impl usb_device::bus::UsbBus for UsbBus {
             inner.usb.u1ieset.write(|w| w
                 .trnie().bit(true)
                 .stallie().bit(true)
-                .urstie_detachie().bit(true));
+                .urstie_detachie().bit(true)
+                .idleie().bit(true)
+                .resumeie().bit(true));
             inner.usb.u1con.write(|w| w.usben_sofen().bit(true));
         })
     }
@@ -524,8 +821,16 @@ impl usb_device::bus::UsbBus for UsbBus {
     }
 
     fn set_stalled(&self, ep_addr: EndpointAddress, stalled: bool) {
-        if stalled {
-            let ep = ep_addr.index();
+        let ep = ep_addr.index();
+        let dir = ep_addr.is_in() as usize;
+        // isochronous endpoints have no handshake and cannot be stalled
+        let iso = critical_section::with(|cs| {
+            self.0.borrow_ref(cs).ecb
+                .get(ep)
+                .and_then(|ecb| ecb[dir].as_ref())
+                .is_some_and(|ecb| is_iso(ecb.ep_type))
+        });
+        if stalled && !iso {
             unsafe {
                 Self::write_epreg(ep, Self::read_epreg(ep) | EPREG_EPSTALL_MASK);
             }
@@ -536,13 +841,45 @@ impl usb_device::bus::UsbBus for UsbBus {
         false
     }
 
-    fn suspend(&self) {}
+    /// Put the USB module into suspend mode
+    ///
+    /// Bus activity is detected by the activity interrupt, which can wake up
+    /// the CPU from Sleep mode.
+    fn suspend(&self) {
+        critical_section::with(|cs| {
+            let mut inner = self.0.borrow_ref_mut(cs);
+            inner.usb.u1otgir.write(|w| w.actvif().bit(true));
+            inner.usb.u1otgieset.write(|w| w.actvie().bit(true));
+            inner.usb.u1pwrcset.write(|w| w.ususpend().bit(true));
+            inner.suspended = true;
+        });
+    }
 
-    fn resume(&self) {}
+    /// Resume the USB module
+    fn resume(&self) {
+        critical_section::with(|cs| {
+            let mut inner = self.0.borrow_ref_mut(cs);
+            inner.usb.u1pwrcclr.write(|w| w.ususpend().bit(true));
+            inner.usb.u1otgieclr.write(|w| w.actvie().bit(true));
+            // ACTVIF cannot be cleared while the USB module is suspended
+            while inner.usb.u1otgir.read().actvif().bit() {
+                inner.usb.u1otgir.write(|w| w.actvif().bit(true));
+            }
+            inner.usb.u1ir.write(|w| w.resumeif().bit(true).idleif().bit(true));
+            inner.suspended = false;
+        });
+    }
 
     fn poll(&self) -> PollResult {
         critical_section::with(|cs| {
             let mut inner = self.0.borrow_ref_mut(cs);
+            if inner.suspended {
+                // resume signaling and bus reset are both bus activity
+                if inner.usb.u1otgir.read().actvif().bit() || inner.usb.u1ir.read().resumeif().bit() {
+                    return PollResult::Resume;
+                }
+                return PollResult::None;
+            }
             let mut pr_in = 0u16;
             let u1eir = inner.usb.u1eir.read().bits();
             if u1eir != 0 {
@@ -602,6 +939,10 @@ impl usb_device::bus::UsbBus for UsbBus {
 
                 return PollResult::Reset;
             }
+            if inner.usb.u1ir.read().idleif().bit() {
+                inner.usb.u1ir.write(|w| w.idleif().bit(true));
+                return PollResult::Suspend;
+            }
             if inner.usb.u1ir.read().stallif().bit() {
                 inner.usb.u1ep0clr.write(|w| w.epstall().bit(true));
                 inner.usb.u1ir.write(|w| w.stallif().bit(true));
@@ -0,0 +1,710 @@
+//! USB host driver for PIC32 devices with Full Speed USB OTG hardware
+//!
+//! Supports a single full-speed or low-speed device attached directly to the
+//! USB port (no hubs). The driver controls VBUS, detects attachment and
+//! detachment of a device, resets the bus, generates SOF packets and performs
+//! blocking control, bulk and interrupt transfers, e.g.
+//!
+//! ```ignore
+//! static mut USB_MEMORY: UsbMemory<0> = UsbMemory::new();
+//!
+//! let mut host = UsbHost::new(p.USB, unsafe { &mut *addr_of_mut!(USB_MEMORY) });
+//! host.set_vbus(true)?;
+//! while host.poll() != Some(HostEvent::Attached) {}
+//! host.reset_bus(&mut delay)?;
+//! let device = host.enumerate(&mut delay)?;
+//! ```
+//!
+//! The transfer functions transfer data directly from and to the buffers
+//! passed to them by means of DMA. These buffers must be located in RAM. The
+//! endpoint buffers of `UsbMemory` are not used so that `UsbMemory<0>` is
+//! sufficient.
+//!
+//! NAKed control and bulk transactions are retried once per frame until a
+//! configurable limit is reached. NAKed interrupt transactions are not
+//! retried. A transaction that has not completed within two frames is aborted
+//! with `Error::Timeout`.
+
+use mips_mcu::fmt::virt_to_phys;
+
+use super::{
+    BufferDescriptorTable, UsbMemory, BD_DATA01, BD_DTS, BD_PID_MSK, BD_PID_POS, BD_UOWN,
+    EPREG_EPCONDIS_MASK, EPREG_EPHSHK_MASK, EPREG_EPRXEN_MASK, EPREG_EPTXEN_MASK, USB_PID_IN,
+    USB_PID_OUT, USB_PID_SETUP,
+};
+use crate::devcfg;
+use crate::pac::USB;
+
+use embedded_hal::delay::DelayNs;
+
+pub use pic32_usb_descriptor::*;
+
+// bit masks for U1CON in host mode
+const U1CON_SOFEN: u32 = 0x01;
+const U1CON_PPBRST: u32 = 0x02;
+const U1CON_HOSTEN: u32 = 0x08;
+const U1CON_USBRST: u32 = 0x10;
+const U1CON_TOKBUSY: u32 = 0x20;
+const U1CON_SE0: u32 = 0x40;
+const U1CON_JSTATE: u32 = 0x80;
+
+// bit masks for U1IR in host mode
+const U1IR_DETACHIF: u32 = 0x01;
+const U1IR_UERRIF: u32 = 0x02;
+const U1IR_SOFIF: u32 = 0x04;
+const U1IR_TRNIF: u32 = 0x08;
+const U1IR_ATTACHIF: u32 = 0x40;
+
+// bit mask for U1EIR
+const U1EIR_BTOEF: u32 = 0x10;
+
+// bit masks for U1OTGCON
+const U1OTGCON_VBUSON: u32 = 0x08;
+const U1OTGCON_OTGEN: u32 = 0x04;
+const U1OTGCON_DMPULDWN: u32 = 0x10;
+const U1OTGCON_DPPULDWN: u32 = 0x20;
+
+// bit masks for U1OTGSTAT
+const U1OTGSTAT_VBUSVD: u32 = 0x01;
+
+// bit masks for U1EP0 in host mode
+const EPREG_RETRYDIS_MASK: u8 = 0x40;
+const EPREG_LSPD_MASK: u8 = 0x80;
+
+// bit mask for U1ADDR
+const U1ADDR_LSPDEN: u32 = 0x80;
+
+/// FVBUSONIO bit of DEVCFG3
+const DEVCFG3_FVBUSONIO: u32 = 1 << 31;
+
+/// SOF threshold for packets of up to 64 bytes (in bytes)
+const SOF_THRESHOLD_64: u32 = 0x4a;
+
+// handshake and data PIDs reported in the buffer descriptor
+const PID_ACK: u8 = 0x2;
+const PID_DATA0: u8 = 0x3;
+const PID_DATA1: u8 = 0xb;
+const PID_NAK: u8 = 0xa;
+const PID_STALL: u8 = 0xe;
+const PID_BUS_TIMEOUT: u8 = 0x0;
+
+/// USB address assigned to the attached device
+const DEVICE_ADDRESS: u8 = 1;
+
+/// Default limit of NAK retries (in frames)
+const DEFAULT_NAK_LIMIT: u32 = 5000;
+
+/// Number of SOF packets after which an uncompleted transaction is aborted
+const TRANSACTION_TIMEOUT_FRAMES: u32 = 2;
+
+/// USB host errors
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// No device attached or the bus has not been reset
+    NotAttached,
+    /// The device was detached during the transfer
+    Detached,
+    /// The endpoint is stalled
+    Stall,
+    /// The device NAKed the transaction (interrupt transfers) or the NAK
+    /// limit has been reached (control and bulk transfers)
+    Nak,
+    /// The device did not respond
+    Timeout,
+    /// CRC, bit stuffing or data toggle error
+    DataError,
+    /// The buffer is too small for the requested transfer
+    BufferTooSmall,
+    /// The device returned an invalid descriptor
+    InvalidDescriptor,
+    /// VBUS cannot be controlled because the VBUSON pin is not controlled by
+    /// the USB module (FVBUSONIO configuration setting)
+    VbusNotControlled,
+}
+
+/// Events reported by `UsbHost::poll()`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HostEvent {
+    /// A device has been attached
+    Attached,
+    /// The device has been detached
+    Detached,
+}
+
+/// Speed of the attached device
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Speed {
+    /// Full speed (12 Mbit/s)
+    Full,
+    /// Low speed (1.5 Mbit/s)
+    Low,
+}
+
+/// USB host driver
+pub struct UsbHost {
+    usb: USB,
+    bdt: &'static mut BufferDescriptorTable,
+    /// next buffer descriptor to use is odd (index 0: RX, 1: TX)
+    next_odd: [bool; 2],
+    attached: bool,
+    speed: Speed,
+    address: u8,
+    max_packet_size0: u16,
+    /// data toggle of the endpoints (bit n: endpoint n, index 0: OUT, 1: IN)
+    toggles: [u16; 2],
+    nak_limit: u32,
+}
+
+impl UsbHost {
+    /// Create a new USB host driver
+    ///
+    /// The USB module is switched to host mode with the D+ and D- pull-down
+    /// resistors enabled. VBUS is not switched on.
+    pub fn new<const N: usize>(usb: USB, memory: &'static mut UsbMemory<N>) -> Self {
+        Self::with_bdt(usb, memory.parts().bdt)
+    }
+
+    /// Create a new USB host driver using a buffer descriptor table lent by
+    /// `Otg`
+    pub(super) fn with_bdt(usb: USB, bdt: &'static mut BufferDescriptorTable) -> Self {
+        usb.u1con.write(|w| unsafe { w.bits(0) });
+        usb.u1ie.write(|w| unsafe { w.bits(0) });
+        usb.u1otgie.write(|w| unsafe { w.bits(0) });
+        usb.u1pwrc.write(|w| w.usbpwr().bit(true));
+        for i in 0..super::N_ENDPOINTS {
+            unsafe { super::UsbBus::write_epreg(i, 0) };
+        }
+        *bdt = BufferDescriptorTable::new();
+        let dma_addr = virt_to_phys(bdt.as_raw()).address() as u32;
+        usb.u1bdtp3.write(|w| unsafe { w.bits(dma_addr >> 24) });
+        usb.u1bdtp2.write(|w| unsafe { w.bits(dma_addr >> 16) });
+        usb.u1bdtp1.write(|w| unsafe { w.bits(dma_addr >> 8) });
+        usb.u1addr.write(|w| unsafe { w.bits(0) });
+        usb.u1sof.write(|w| unsafe { w.bits(SOF_THRESHOLD_64) });
+        usb.u1otgcon
+            .write(|w| unsafe { w.bits(U1OTGCON_OTGEN | U1OTGCON_DPPULDWN | U1OTGCON_DMPULDWN) });
+        usb.u1con
+            .write(|w| unsafe { w.bits(U1CON_HOSTEN | U1CON_PPBRST) });
+        usb.u1conclr.write(|w| unsafe { w.bits(U1CON_PPBRST) });
+        usb.u1ir.write(|w| unsafe { w.bits(0xff) });
+        usb.u1eir.write(|w| unsafe { w.bits(0xff) });
+        // all error conditions set UERRIF
+        usb.u1eie.write(|w| unsafe { w.bits(0xff) });
+        UsbHost {
+            usb,
+            bdt,
+            next_odd: [false; 2],
+            attached: false,
+            speed: Speed::Full,
+            address: 0,
+            max_packet_size0: 8,
+            toggles: [0; 2],
+            nak_limit: DEFAULT_NAK_LIMIT,
+        }
+    }
+
+    /// Switch off the USB module and VBUS and return the USB peripheral
+    pub fn free(self) -> USB {
+        self.into_parts().0
+    }
+
+    /// Switch off the USB module and VBUS and return the USB peripheral and
+    /// the buffer descriptor table
+    pub(super) fn into_parts(self) -> (USB, &'static mut BufferDescriptorTable) {
+        self.usb.u1con.write(|w| unsafe { w.bits(0) });
+        self.usb.u1otgcon.write(|w| unsafe { w.bits(0) });
+        self.usb.u1pwrc.write(|w| unsafe { w.bits(0) });
+        (self.usb, self.bdt)
+    }
+
+    /// Switch VBUS on or off by means of the VBUSON pin
+    ///
+    /// Returns an error if the VBUSON pin is not controlled by the USB module
+    /// (FVBUSONIO configuration setting). In this case, VBUS must be switched
+    /// by other means, e.g. a GPIO pin.
+    pub fn set_vbus(&mut self, on: bool) -> Result<(), Error> {
+        if devcfg::devcfg3() & DEVCFG3_FVBUSONIO == 0 {
+            return Err(Error::VbusNotControlled);
+        }
+        if on {
+            self.usb
+                .u1otgconset
+                .write(|w| unsafe { w.bits(U1OTGCON_VBUSON) });
+        } else {
+            self.usb
+                .u1otgconclr
+                .write(|w| unsafe { w.bits(U1OTGCON_VBUSON) });
+        }
+        Ok(())
+    }
+
+    /// Check if VBUS is above the VBUS valid threshold
+    pub fn is_vbus_valid(&self) -> bool {
+        self.usb.u1otgstat.read().bits() & U1OTGSTAT_VBUSVD != 0
+    }
+
+    /// Set the maximum number of frames a NAKed control or bulk transaction is
+    /// retried
+    pub fn set_nak_limit(&mut self, frames: u32) {
+        self.nak_limit = frames;
+    }
+
+    /// Check for attachment or detachment of a device
+    ///
+    /// After attachment, the bus must be reset with `reset_bus()`.
+    pub fn poll(&mut self) -> Option<HostEvent> {
+        let ir = self.usb.u1ir.read().bits();
+        if ir & U1IR_DETACHIF != 0 {
+            self.usb
+                .u1ir
+                .write(|w| unsafe { w.bits(U1IR_DETACHIF | U1IR_ATTACHIF) });
+            self.usb.u1conclr.write(|w| unsafe { w.bits(U1CON_SOFEN) });
+            self.usb.u1addr.write(|w| unsafe { w.bits(0) });
+            let was_attached = self.attached;
+            self.attached = false;
+            return was_attached.then_some(HostEvent::Detached);
+        }
+        if ir & U1IR_ATTACHIF != 0 {
+            self.usb.u1ir.write(|w| unsafe { w.bits(U1IR_ATTACHIF) });
+            if !self.attached {
+                self.attached = true;
+                return Some(HostEvent::Attached);
+            }
+        }
+        None
+    }
+
+    /// Check if a device is attached
+    pub fn is_attached(&self) -> bool {
+        self.attached
+    }
+
+    /// Speed of the attached device as determined by `reset_bus()`
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Reset the bus and start generating SOF packets
+    ///
+    /// Waits 100 ms for the connection to settle, detects the speed of the
+    /// device, drives reset signaling for 50 ms and waits 10 ms for reset
+    /// recovery. The device has the default address 0 afterwards.
+    pub fn reset_bus(&mut self, delay: &mut impl DelayNs) -> Result<Speed, Error> {
+        if !self.attached {
+            return Err(Error::NotAttached);
+        }
+        delay.delay_ms(100);
+        let con = self.usb.u1con.read().bits();
+        if con & U1CON_SE0 != 0 {
+            return Err(Error::NotAttached);
+        }
+        // the J state of a full-speed device is the K state of a low-speed
+        // device
+        self.speed = if con & U1CON_JSTATE != 0 {
+            Speed::Full
+        } else {
+            Speed::Low
+        };
+        self.usb.u1conclr.write(|w| unsafe { w.bits(U1CON_SOFEN) });
+        self.usb.u1conset.write(|w| unsafe { w.bits(U1CON_USBRST) });
+        delay.delay_ms(50);
+        self.usb.u1conclr.write(|w| unsafe { w.bits(U1CON_USBRST) });
+        self.usb.u1conset.write(|w| unsafe { w.bits(U1CON_SOFEN) });
+        delay.delay_ms(10);
+        self.address = 0;
+        self.max_packet_size0 = 8;
+        self.toggles = [0; 2];
+        Ok(self.speed)
+    }
+
+    /// Get the 11-bit number of the current frame
+    pub fn frame_number(&self) -> u16 {
+        let low = self.usb.u1frml.read().bits() as u16 & 0xff;
+        let high = self.usb.u1frmh.read().bits() as u16 & 0x07;
+        high << 8 | low
+    }
+
+    /// Enumerate the attached device
+    ///
+    /// Reads the device descriptor and assigns an address to the device. The
+    /// device must be configured afterwards, e.g. by reading the configuration
+    /// descriptor with `get_configuration_descriptor()` and calling
+    /// `set_configuration()`.
+    pub fn enumerate(&mut self, delay: &mut impl DelayNs) -> Result<DeviceDescriptor, Error> {
+        let mut buf = [0u8; DeviceDescriptor::LENGTH];
+        self.address = 0;
+        self.max_packet_size0 = 8;
+        let len = self.control_in(
+            &SetupPacket::get_descriptor(DESCRIPTOR_DEVICE, 0, 8),
+            &mut buf,
+        )?;
+        if len < 8 || !matches!(buf[7], 8 | 16 | 32 | 64) {
+            return Err(Error::InvalidDescriptor);
+        }
+        self.max_packet_size0 = buf[7] as u16;
+        self.control_out(&SetupPacket::set_address(DEVICE_ADDRESS), &[])?;
+        // set address recovery interval
+        delay.delay_ms(2);
+        self.address = DEVICE_ADDRESS;
+        let len = self.control_in(
+            &SetupPacket::get_descriptor(DESCRIPTOR_DEVICE, 0, DeviceDescriptor::LENGTH as u16),
+            &mut buf,
+        )?;
+        DeviceDescriptor::parse(&buf[..len]).ok_or(Error::InvalidDescriptor)
+    }
+
+    /// Read the configuration descriptor with index `index` including all
+    /// interface, endpoint and class specific descriptors into `buf`
+    ///
+    /// Returns the number of bytes read. The descriptors can be iterated by
+    /// means of `Descriptors`.
+    pub fn get_configuration_descriptor(
+        &mut self,
+        index: u8,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut header = [0u8; ConfigurationDescriptor::LENGTH];
+        let len = self.control_in(
+            &SetupPacket::get_descriptor(
+                DESCRIPTOR_CONFIGURATION,
+                index,
+                ConfigurationDescriptor::LENGTH as u16,
+            ),
+            &mut header,
+        )?;
+        let config =
+            ConfigurationDescriptor::parse(&header[..len]).ok_or(Error::InvalidDescriptor)?;
+        let total_length = config.total_length as usize;
+        if total_length > buf.len() {
+            return Err(Error::BufferTooSmall);
+        }
+        self.control_in(
+            &SetupPacket::get_descriptor(DESCRIPTOR_CONFIGURATION, index, config.total_length),
+            &mut buf[..total_length],
+        )
+    }
+
+    /// Select a configuration of the device
+    ///
+    /// Resets the data toggles of all endpoints.
+    pub fn set_configuration(&mut self, value: u8) -> Result<(), Error> {
+        self.control_out(&SetupPacket::set_configuration(value), &[])?;
+        self.toggles = [0; 2];
+        Ok(())
+    }
+
+    /// Clear the halt condition of a stalled endpoint
+    ///
+    /// `ep_addr` is the endpoint address including the direction bit. Resets
+    /// the data toggle of the endpoint.
+    pub fn clear_halt(&mut self, ep_addr: u8) -> Result<(), Error> {
+        self.control_out(&SetupPacket::clear_halt(ep_addr), &[])?;
+        let dir = (ep_addr >> 7) as usize;
+        self.toggles[dir] &= !(1 << (ep_addr & 0x0f));
+        Ok(())
+    }
+
+    /// Perform a control transfer with a device-to-host data stage
+    ///
+    /// Returns the number of bytes received, which may be less than
+    /// `setup.length`.
+    pub fn control_in(&mut self, setup: &SetupPacket, buf: &mut [u8]) -> Result<usize, Error> {
+        let length = setup.length as usize;
+        if buf.len() < length {
+            return Err(Error::BufferTooSmall);
+        }
+        self.setup_stage(setup)?;
+        let mps = self.max_packet_size0 as usize;
+        let mut received = 0;
+        let mut data01 = true;
+        while received < length {
+            let chunk = &mut buf[received..length.min(received + mps)];
+            let n = self.transfer(USB_PID_IN, 0, data01, true, chunk.as_mut_ptr(), chunk.len())?;
+            received += n;
+            data01 = !data01;
+            if n < mps {
+                break;
+            }
+        }
+        // status stage
+        self.transfer(USB_PID_OUT, 0, true, true, buf.as_mut_ptr(), 0)?;
+        Ok(received)
+    }
+
+    /// Perform a control transfer with a host-to-device data stage or without
+    /// data stage
+    pub fn control_out(&mut self, setup: &SetupPacket, data: &[u8]) -> Result<(), Error> {
+        let length = setup.length as usize;
+        if data.len() < length {
+            return Err(Error::BufferTooSmall);
+        }
+        self.setup_stage(setup)?;
+        let mps = self.max_packet_size0 as usize;
+        let mut data01 = true;
+        for chunk in data[..length].chunks(mps) {
+            self.transfer(
+                USB_PID_OUT,
+                0,
+                data01,
+                true,
+                chunk.as_ptr() as *mut u8,
+                chunk.len(),
+            )?;
+            data01 = !data01;
+        }
+        // status stage
+        let mut status = [0u8; 0];
+        self.transfer(USB_PID_IN, 0, true, true, status.as_mut_ptr(), 0)?;
+        Ok(())
+    }
+
+    /// Receive data from a bulk IN endpoint
+    ///
+    /// Receives packets until a short packet is received or `buf` is full.
+    /// The length of `buf` should be a multiple of `max_packet_size` or the
+    /// expected length of the data. Returns the number of bytes received.
+    pub fn bulk_in(
+        &mut self,
+        ep: u8,
+        max_packet_size: u16,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mps = max_packet_size as usize;
+        let len = buf.len();
+        let mut received = 0;
+        while received < len {
+            let chunk = &mut buf[received..(received + mps).min(len)];
+            let n = self.data_transaction(USB_PID_IN, ep, true, chunk.as_mut_ptr(), chunk.len())?;
+            received += n;
+            if n < mps {
+                break;
+            }
+        }
+        Ok(received)
+    }
+
+    /// Send data to a bulk OUT endpoint
+    ///
+    /// No zero-length packet is sent if the length of `data` is a multiple of
+    /// `max_packet_size`.
+    pub fn bulk_out(&mut self, ep: u8, max_packet_size: u16, data: &[u8]) -> Result<(), Error> {
+        for chunk in data.chunks(max_packet_size as usize) {
+            self.data_transaction(
+                USB_PID_OUT,
+                ep,
+                true,
+                chunk.as_ptr() as *mut u8,
+                chunk.len(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Receive a packet from an interrupt IN endpoint
+    ///
+    /// Returns `WouldBlock` if the device has no data (NAK). The polling
+    /// interval is up to the caller. The length of `buf` must be at least the
+    /// maximum packet size of the endpoint.
+    pub fn interrupt_in(&mut self, ep: u8, buf: &mut [u8]) -> nb::Result<usize, Error> {
+        self.data_transaction(USB_PID_IN, ep, false, buf.as_mut_ptr(), buf.len())
+            .map_err(|e| match e {
+                Error::Nak => nb::Error::WouldBlock,
+                e => nb::Error::Other(e),
+            })
+    }
+
+    /// Send a packet to an interrupt OUT endpoint
+    ///
+    /// Returns `WouldBlock` if the device cannot accept the data (NAK).
+    pub fn interrupt_out(&mut self, ep: u8, data: &[u8]) -> nb::Result<(), Error> {
+        self.data_transaction(USB_PID_OUT, ep, false, data.as_ptr() as *mut u8, data.len())
+            .map(|_| ())
+            .map_err(|e| match e {
+                Error::Nak => nb::Error::WouldBlock,
+                e => nb::Error::Other(e),
+            })
+    }
+
+    fn setup_stage(&mut self, setup: &SetupPacket) -> Result<(), Error> {
+        if !self.attached {
+            return Err(Error::NotAttached);
+        }
+        let mut packet = setup.to_bytes();
+        self.transfer(
+            USB_PID_SETUP,
+            0,
+            false,
+            true,
+            packet.as_mut_ptr(),
+            packet.len(),
+        )?;
+        Ok(())
+    }
+
+    /// Data transaction of a bulk or interrupt endpoint with data toggle
+    /// tracking
+    fn data_transaction(
+        &mut self,
+        pid: u8,
+        ep: u8,
+        retry_nak: bool,
+        buf: *mut u8,
+        len: usize,
+    ) -> Result<usize, Error> {
+        if !self.attached {
+            return Err(Error::NotAttached);
+        }
+        let dir = (pid == USB_PID_IN) as usize;
+        let mask = 1 << (ep & 0x0f);
+        let data01 = self.toggles[dir] & mask != 0;
+        let n = self.transfer(pid, ep, data01, retry_nak, buf, len)?;
+        self.toggles[dir] ^= mask;
+        Ok(n)
+    }
+
+    /// Perform a transaction and retry NAKed transactions once per frame if
+    /// `retry_nak` is set
+    fn transfer(
+        &mut self,
+        pid: u8,
+        ep: u8,
+        data01: bool,
+        retry_nak: bool,
+        buf: *mut u8,
+        len: usize,
+    ) -> Result<usize, Error> {
+        let mut naks = 0;
+        loop {
+            match self.transaction(pid, ep, data01, buf, len) {
+                Err(Error::Nak) if retry_nak && naks < self.nak_limit => {
+                    naks += 1;
+                    self.wait_frame()?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Check if SOF packets are generated, which bounds the waiting time in
+    /// `wait_frame()` and `transaction()`
+    fn sof_enabled(&self) -> bool {
+        self.usb.u1con.read().bits() & U1CON_SOFEN != 0
+    }
+
+    /// Wait for the next SOF packet
+    fn wait_frame(&mut self) -> Result<(), Error> {
+        if !self.sof_enabled() {
+            return Err(Error::NotAttached);
+        }
+        self.usb.u1ir.write(|w| unsafe { w.bits(U1IR_SOFIF) });
+        loop {
+            let ir = self.usb.u1ir.read().bits();
+            if ir & U1IR_DETACHIF != 0 {
+                return Err(Error::Detached);
+            }
+            if ir & U1IR_UERRIF != 0 {
+                return Err(take_error(&self.usb));
+            }
+            if ir & U1IR_SOFIF != 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Perform a single transaction
+    fn transaction(
+        &mut self,
+        pid: u8,
+        ep: u8,
+        data01: bool,
+        buf: *mut u8,
+        len: usize,
+    ) -> Result<usize, Error> {
+        if !self.sof_enabled() {
+            return Err(Error::NotAttached);
+        }
+        // the endpoint 0 register controls all host transactions
+        let mut epreg =
+            EPREG_EPHSHK_MASK | EPREG_EPTXEN_MASK | EPREG_EPRXEN_MASK | EPREG_RETRYDIS_MASK;
+        if ep != 0 {
+            epreg |= EPREG_EPCONDIS_MASK;
+        }
+        let mut addr = self.address as u32;
+        if self.speed == Speed::Low {
+            epreg |= EPREG_LSPD_MASK;
+            addr |= U1ADDR_LSPDEN;
+        }
+        unsafe { super::UsbBus::write_epreg(0, epreg) };
+        self.usb.u1addr.write(|w| unsafe { w.bits(addr) });
+
+        let dir = (pid != USB_PID_IN) as usize;
+        let odd = self.next_odd[dir];
+        self.next_odd[dir] = !odd;
+        let bd = unsafe { &mut self.bdt.ep_dir_ppbi[0][dir][odd as usize] };
+        bd.set_buffer_address(virt_to_phys(buf));
+        bd.set_byte_count(len as u16);
+        bd.set_flags(BD_UOWN | if data01 { BD_DATA01 } else { 0 } | BD_DTS);
+
+        while self.usb.u1con.read().bits() & U1CON_TOKBUSY != 0 {}
+        self.usb
+            .u1ir
+            .write(|w| unsafe { w.bits(U1IR_TRNIF | U1IR_UERRIF | U1IR_SOFIF) });
+        self.usb
+            .u1tok
+            .write(|w| unsafe { w.bits((pid as u32) << 4 | (ep & 0x0f) as u32) });
+        let mut frames = 0;
+        let result = loop {
+            let ir = self.usb.u1ir.read().bits();
+            if ir & U1IR_DETACHIF != 0 {
+                break Err(Error::Detached);
+            }
+            if ir & U1IR_UERRIF != 0 {
+                break Err(take_error(&self.usb));
+            }
+            if ir & U1IR_TRNIF != 0 {
+                break Ok(());
+            }
+            if ir & U1IR_SOFIF != 0 {
+                self.usb.u1ir.write(|w| unsafe { w.bits(U1IR_SOFIF) });
+                frames += 1;
+                if frames > TRANSACTION_TIMEOUT_FRAMES {
+                    break Err(Error::Timeout);
+                }
+            }
+        };
+        self.usb.u1ir.write(|w| unsafe { w.bits(U1IR_TRNIF) });
+        if let Err(error) = result {
+            if bd.flags() & BD_UOWN != 0 {
+                // take back the buffer descriptor; the ping-pong buffer
+                // pointers of the USB module did not advance
+                bd.set_flags(0);
+                self.usb.u1conset.write(|w| unsafe { w.bits(U1CON_PPBRST) });
+                self.usb.u1conclr.write(|w| unsafe { w.bits(U1CON_PPBRST) });
+                self.next_odd = [false; 2];
+            }
+            return Err(error);
+        }
+        let eir = self.usb.u1eir.read().bits();
+        self.usb.u1eir.write(|w| unsafe { w.bits(eir) });
+
+        match ((bd.flags() & BD_PID_MSK) >> BD_PID_POS) as u8 {
+            PID_ACK | PID_DATA0 | PID_DATA1 => Ok(bd.byte_count() as usize),
+            PID_NAK => Err(Error::Nak),
+            PID_STALL => Err(Error::Stall),
+            PID_BUS_TIMEOUT => Err(Error::Timeout),
+            _ => Err(Error::DataError),
+        }
+    }
+}
+
+/// Clear the USB error flags and map them to an `Error`
+fn take_error(usb: &USB) -> Error {
+    let eir = usb.u1eir.read().bits();
+    usb.u1eir.write(|w| unsafe { w.bits(eir) });
+    usb.u1ir.write(|w| unsafe { w.bits(U1IR_UERRIF) });
+    if eir & U1EIR_BTOEF != 0 {
+        Error::Timeout
+    } else {
+        Error::DataError
+    }
+}
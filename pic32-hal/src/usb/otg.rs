@@ -0,0 +1,354 @@
+//! USB OTG role detection and switching
+//!
+//! `Otg` owns the USB peripheral and a `UsbMemory` and hands them out either
+//! to the USB device driver (`UsbBus`) or to the USB host driver (`UsbHost`)
+//! depending on the state of the ID pin. A mini-A/micro-A plug pulls the ID
+//! pin low, which selects the host role. Otherwise, the device role is
+//! selected. The ID pin must be controlled by the USB module (FUSBIDIO
+//! configuration setting), e.g.
+//!
+//! ```ignore
+//! let mut otg = Otg::new(p.USB, unsafe { &mut *addr_of_mut!(USB_MEMORY) });
+//! loop {
+//!     match otg.id_role()? {
+//!         Role::Device => {
+//!             let usb_bus = otg.start_device()?;
+//!             // run the usb-device stack until the ID pin changes
+//!             otg.stop_device(usb_bus).ok();
+//!         }
+//!         Role::Host => {
+//!             let host = otg.start_host()?;
+//!             // access the attached device until the ID pin changes
+//!             otg.stop_host(host).ok();
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The status and interrupt flags of the OTG module can be read in any role.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use usb_device::bus::UsbBusAllocator;
+
+use super::host::UsbHost;
+use super::{BufferDescriptorTable, MemoryParts, UsbBus, UsbMemory};
+use crate::devcfg;
+use crate::pac::USB;
+
+use embedded_hal::delay::DelayNs;
+
+// bit masks for U1OTGSTAT and U1OTGIR
+const OTG_VBUSVD: u32 = 0x01;
+const OTG_SESEND: u32 = 0x04;
+const OTG_SESVD: u32 = 0x08;
+const OTG_LSTATE: u32 = 0x20;
+const OTG_ID: u32 = 0x80;
+
+// bit masks for U1OTGCON
+const U1OTGCON_VBUSDIS: u32 = 0x01;
+const U1OTGCON_VBUSCHG: u32 = 0x02;
+const U1OTGCON_OTGEN: u32 = 0x04;
+const U1OTGCON_DPPULUP: u32 = 0x80;
+
+/// FUSBIDIO bit of DEVCFG3
+const DEVCFG3_FUSBIDIO: u32 = 1 << 30;
+
+/// USB peripheral and memory handed back by a dropped `UsbBus` of the device
+/// role
+static RELEASED_DEVICE: Mutex<RefCell<Option<(USB, MemoryParts)>>> = Mutex::new(RefCell::new(None));
+
+/// Called by `UsbBus::drop()` for a `UsbBus` created by `Otg::start_device()`
+pub(super) fn release_device(usb: USB, parts: MemoryParts) {
+    critical_section::with(|cs| {
+        RELEASED_DEVICE.borrow_ref_mut(cs).replace((usb, parts));
+    });
+}
+
+/// OTG errors
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The ID pin is not controlled by the USB module (FUSBIDIO
+    /// configuration setting)
+    IdNotControlled,
+    /// The requested operation is not possible in the current role
+    RoleActive,
+    /// A session is already active (VBUS is above the session end threshold)
+    SessionActive,
+}
+
+/// USB role
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Role {
+    /// USB device (B-device)
+    Device,
+    /// USB host (A-device)
+    Host,
+}
+
+/// State of the OTG module
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct OtgStatus {
+    /// Role selected by the ID pin; `None` if the ID pin is not controlled by
+    /// the USB module
+    pub id_role: Option<Role>,
+    /// VBUS is above the VBUS valid threshold (A-device)
+    pub vbus_valid: bool,
+    /// VBUS is above the session valid threshold (B-device)
+    pub session_valid: bool,
+    /// VBUS is below the session end threshold
+    pub session_end: bool,
+    /// The bus lines have been stable for at least 1 ms
+    pub line_stable: bool,
+}
+
+/// OTG events reported by `Otg::poll()`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OtgEvent {
+    /// The state of the ID pin changed
+    IdChanged,
+    /// VBUS crossed the VBUS valid threshold
+    VbusValidChanged,
+    /// VBUS crossed the session valid threshold. On an A-device with VBUS
+    /// switched off, this indicates a session request (VBUS pulsing) of the
+    /// B-device.
+    SessionValidChanged,
+    /// VBUS crossed the session end threshold
+    SessionEndChanged,
+}
+
+/// USB OTG manager
+pub struct Otg {
+    /// USB peripheral while no role is active
+    usb: Option<USB>,
+    /// buffer descriptor table while no role is active
+    bdt: Option<&'static mut BufferDescriptorTable>,
+    /// USB buffers while the device role is not active
+    buffers: Option<&'static mut [u8]>,
+    role: Option<Role>,
+}
+
+impl Otg {
+    /// Create a new OTG manager
+    ///
+    /// The USB module is powered up to monitor the ID pin and VBUS but no role
+    /// is started.
+    pub fn new<const N: usize>(usb: USB, memory: &'static mut UsbMemory<N>) -> Self {
+        usb.u1con.write(|w| unsafe { w.bits(0) });
+        usb.u1ie.write(|w| unsafe { w.bits(0) });
+        usb.u1otgie.write(|w| unsafe { w.bits(0) });
+        usb.u1otgcon.write(|w| unsafe { w.bits(U1OTGCON_OTGEN) });
+        usb.u1pwrc.write(|w| w.usbpwr().bit(true));
+        usb.u1otgir.write(|w| unsafe { w.bits(0xff) });
+        let parts = memory.parts();
+        Otg {
+            usb: Some(usb),
+            bdt: Some(parts.bdt),
+            buffers: Some(parts.buffers),
+            role: None,
+        }
+    }
+
+    /// Return the USB peripheral
+    ///
+    /// Returns an error if a role is active. The `UsbMemory` remains borrowed.
+    pub fn free(mut self) -> Result<USB, (Error, Self)> {
+        self.reclaim_device();
+        match self.usb.take() {
+            Some(usb) if self.role.is_none() => {
+                usb.u1otgcon.write(|w| unsafe { w.bits(0) });
+                usb.u1pwrc.write(|w| unsafe { w.bits(0) });
+                Ok(usb)
+            }
+            usb => {
+                self.usb = usb;
+                Err((Error::RoleActive, self))
+            }
+        }
+    }
+
+    /// Read the OTG status and interrupt flag registers
+    ///
+    /// Only these registers are accessed while a role is active; reading them
+    /// has no side effects and clearing flags does not affect other bits.
+    fn regs(&self) -> &crate::pac::usb::RegisterBlock {
+        unsafe { &*USB::ptr() }
+    }
+
+    /// Get the status of the OTG module
+    pub fn status(&self) -> OtgStatus {
+        let stat = self.regs().u1otgstat.read().bits();
+        let id_role = if devcfg::devcfg3() & DEVCFG3_FUSBIDIO == 0 {
+            None
+        } else if stat & OTG_ID == 0 {
+            Some(Role::Host)
+        } else {
+            Some(Role::Device)
+        };
+        OtgStatus {
+            id_role,
+            vbus_valid: stat & OTG_VBUSVD != 0,
+            session_valid: stat & OTG_SESVD != 0,
+            session_end: stat & OTG_SESEND != 0,
+            line_stable: stat & OTG_LSTATE != 0,
+        }
+    }
+
+    /// Get the role selected by the ID pin
+    pub fn id_role(&self) -> Result<Role, Error> {
+        self.status().id_role.ok_or(Error::IdNotControlled)
+    }
+
+    /// Get the active role
+    pub fn role(&self) -> Option<Role> {
+        self.role
+    }
+
+    /// Check for changes of the ID pin and the VBUS comparators
+    ///
+    /// Reports one event per call. The corresponding flag is cleared.
+    pub fn poll(&mut self) -> Option<OtgEvent> {
+        let regs = self.regs();
+        let ir = regs.u1otgir.read().bits();
+        let (mask, event) = if ir & OTG_ID != 0 {
+            (OTG_ID, OtgEvent::IdChanged)
+        } else if ir & OTG_VBUSVD != 0 {
+            (OTG_VBUSVD, OtgEvent::VbusValidChanged)
+        } else if ir & OTG_SESVD != 0 {
+            (OTG_SESVD, OtgEvent::SessionValidChanged)
+        } else if ir & OTG_SESEND != 0 {
+            (OTG_SESEND, OtgEvent::SessionEndChanged)
+        } else {
+            return None;
+        };
+        regs.u1otgir.write(|w| unsafe { w.bits(mask) });
+        Some(event)
+    }
+
+    /// Check if the role selected by the ID pin differs from the active role
+    pub fn role_change_required(&self) -> bool {
+        match (self.role, self.status().id_role) {
+            (Some(active), Some(id)) => active != id,
+            _ => false,
+        }
+    }
+
+    /// Initiate a session as B-device by means of the session request
+    /// protocol (SRP)
+    ///
+    /// Performs data-line pulsing (5 ms) followed by VBUS pulsing (10 ms).
+    /// The A-device is expected to switch on VBUS afterwards, which can be
+    /// detected with `status()` or `poll()`. No role must be active and VBUS
+    /// must be below the session end threshold.
+    pub fn request_session(&mut self, delay: &mut impl DelayNs) -> Result<(), Error> {
+        self.reclaim_device();
+        let usb = match (&self.usb, self.role) {
+            (Some(usb), None) => usb,
+            _ => return Err(Error::RoleActive),
+        };
+        if usb.u1otgstat.read().bits() & OTG_SESEND == 0 {
+            return Err(Error::SessionActive);
+        }
+        usb.u1otgconset
+            .write(|w| unsafe { w.bits(U1OTGCON_DPPULUP) });
+        delay.delay_ms(5);
+        usb.u1otgconclr
+            .write(|w| unsafe { w.bits(U1OTGCON_DPPULUP) });
+        usb.u1otgconset
+            .write(|w| unsafe { w.bits(U1OTGCON_VBUSCHG) });
+        delay.delay_ms(10);
+        usb.u1otgconclr
+            .write(|w| unsafe { w.bits(U1OTGCON_VBUSCHG) });
+        // discharge VBUS in case the A-device does not respond
+        usb.u1otgconset
+            .write(|w| unsafe { w.bits(U1OTGCON_VBUSDIS) });
+        delay.delay_ms(1);
+        usb.u1otgconclr
+            .write(|w| unsafe { w.bits(U1OTGCON_VBUSDIS) });
+        Ok(())
+    }
+
+    /// Take the USB peripheral and the buffer descriptor table for a role
+    fn take(&mut self, role: Role) -> Result<(USB, &'static mut BufferDescriptorTable), Error> {
+        self.reclaim_device();
+        if self.role.is_some() {
+            return Err(Error::RoleActive);
+        }
+        let (usb, bdt) = match (self.usb.take(), self.bdt.take()) {
+            (Some(usb), Some(bdt)) => (usb, bdt),
+            _ => unreachable!(),
+        };
+        self.role = Some(role);
+        Ok((usb, bdt))
+    }
+
+    /// Return to the idle state after a role has been stopped
+    fn release(&mut self, usb: USB, bdt: &'static mut BufferDescriptorTable) {
+        usb.u1con.write(|w| unsafe { w.bits(0) });
+        usb.u1otgcon.write(|w| unsafe { w.bits(U1OTGCON_OTGEN) });
+        usb.u1pwrc.write(|w| w.usbpwr().bit(true));
+        self.usb = Some(usb);
+        self.bdt = Some(bdt);
+        self.role = None;
+    }
+
+    /// Return to the idle state if the `UsbBus` of the device role has been
+    /// dropped
+    fn reclaim_device(&mut self) {
+        if self.role != Some(Role::Device) {
+            return;
+        }
+        if let Some((usb, parts)) = critical_section::with(|cs| RELEASED_DEVICE.take(cs)) {
+            self.buffers = Some(parts.buffers);
+            self.release(usb, parts.bdt);
+        }
+    }
+
+    /// Start the device role
+    ///
+    /// Returns the `UsbBusAllocator` to be used with the usb-device crate.
+    pub fn start_device(&mut self) -> Result<UsbBusAllocator<UsbBus>, Error> {
+        let (usb, bdt) = self.take(Role::Device)?;
+        let buffers = self.buffers.take().unwrap();
+        Ok(UsbBus::with_parts(usb, MemoryParts { bdt, buffers }, true))
+    }
+
+    /// Stop the device role
+    ///
+    /// The `UsbBusAllocator` returned by `start_device()` must be passed back,
+    /// which requires all objects borrowing it (`UsbDevice`, USB classes) to
+    /// be dropped. Returns an error if the device role is not active.
+    #[allow(clippy::result_large_err)]
+    pub fn stop_device(
+        &mut self,
+        usb_bus: UsbBusAllocator<UsbBus>,
+    ) -> Result<(), (Error, UsbBusAllocator<UsbBus>)> {
+        if self.role != Some(Role::Device) {
+            return Err((Error::RoleActive, usb_bus));
+        }
+        // The device role owns the only USB peripheral so that usb_bus must be
+        // the one created by start_device(). Dropping it hands the USB
+        // peripheral and the memory back.
+        drop(usb_bus);
+        self.reclaim_device();
+        Ok(())
+    }
+
+    /// Start the host role
+    pub fn start_host(&mut self) -> Result<UsbHost, Error> {
+        let (usb, bdt) = self.take(Role::Host)?;
+        Ok(UsbHost::with_bdt(usb, bdt))
+    }
+
+    /// Stop the host role
+    ///
+    /// VBUS is switched off. Returns an error if the host role is not active.
+    pub fn stop_host(&mut self, host: UsbHost) -> Result<(), (Error, UsbHost)> {
+        if self.role != Some(Role::Host) {
+            return Err((Error::RoleActive, host));
+        }
+        let (usb, bdt) = host.into_parts();
+        self.release(usb, bdt);
+        Ok(())
+    }
+}
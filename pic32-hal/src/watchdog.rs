@@ -0,0 +1,171 @@
+//! Watchdog timer (WDT)
+//!
+//! The watchdog timer is clocked by the LPRC. Its timeout period is set by
+//! the WDTPS configuration setting and cannot be changed at runtime. If the
+//! FWDTEN configuration setting is on, the watchdog timer is always enabled.
+//! Otherwise, it can be enabled and disabled by software.
+//!
+//! In window mode, the watchdog timer must not be fed before the window is
+//! open, i.e. not earlier than the window size before the timeout. Otherwise,
+//! a reset is generated immediately. Window mode is always active if the
+//! WINDIS configuration setting is cleared. Otherwise, it can be enabled and
+//! disabled by software.
+//!
+//! The supported devices do not include a deadman timer.
+
+use crate::devcfg;
+use crate::pac::WDT;
+use crate::reset::{self, ResetCause};
+
+// bit masks and positions of DEVCFG1 related to the watchdog timer
+const DEVCFG1_WDTPS_MASK: u32 = 0x001f_0000;
+const DEVCFG1_WDTPS_POSITION: usize = 16;
+const DEVCFG1_WINDIS_MASK: u32 = 0x0040_0000;
+const DEVCFG1_FWDTEN_MASK: u32 = 0x0080_0000;
+const DEVCFG1_FWDTWINSZ_MASK: u32 = 0x0300_0000;
+const DEVCFG1_FWDTWINSZ_POSITION: usize = 24;
+
+/// Watchdog errors
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Error {
+    /// The requested setting is overridden by the configuration words
+    ForcedByConfiguration,
+}
+
+/// Size of the window in window mode relative to the timeout period
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum WindowSize {
+    /// Window is 75% of the timeout period
+    Percent75,
+    /// Window is 50% of the timeout period
+    Percent50,
+    /// Window is 37.5% of the timeout period
+    Percent37_5,
+    /// Window is 25% of the timeout period
+    Percent25,
+}
+
+/// Watchdog timer
+pub struct Watchdog {
+    wdt: WDT,
+}
+
+impl Watchdog {
+    /// Create a new `Watchdog` instance
+    ///
+    /// Does not change the state of the watchdog timer.
+    pub fn new(wdt: WDT) -> Self {
+        Watchdog { wdt }
+    }
+
+    /// Return the WDT peripheral consuming the `Watchdog` instance
+    pub fn free(self) -> WDT {
+        self.wdt
+    }
+
+    /// Check if the watchdog timer is always enabled by the configuration
+    /// words (FWDTEN)
+    pub fn is_forced_on(&self) -> bool {
+        devcfg::devcfg1() & DEVCFG1_FWDTEN_MASK != 0
+    }
+
+    /// Check if the watchdog timer is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.is_forced_on() || self.wdt.cont.read().on().bit()
+    }
+
+    /// Feed the watchdog timer
+    pub fn feed(&mut self) {
+        #[cfg(feature = "pic32mx2x4fxxxb")]
+        unsafe {
+            // write the key to the upper half word (WDTCLRKEY)
+            let wdtclrkey = (&self.wdt.cont as *const _ as usize + 2) as *mut u16;
+            core::ptr::write_volatile(wdtclrkey, 0x5743);
+        }
+        #[cfg(not(feature = "pic32mx2x4fxxxb"))]
+        self.wdt.contset.write(|w| w.clr().bit(true));
+    }
+
+    /// Enable the watchdog timer
+    ///
+    /// The watchdog timer is fed before being enabled.
+    pub fn enable(&mut self) {
+        self.feed();
+        self.wdt.contset.write(|w| w.on().bit(true));
+    }
+
+    /// Disable the watchdog timer
+    ///
+    /// Returns a `ForcedByConfiguration` error if the watchdog timer is
+    /// always enabled by the configuration words.
+    pub fn disable(&mut self) -> Result<(), Error> {
+        if self.is_forced_on() {
+            return Err(Error::ForcedByConfiguration);
+        }
+        self.wdt.contclr.write(|w| w.on().bit(true));
+        Ok(())
+    }
+
+    /// Check if the window mode is always enabled by the configuration words
+    /// (WINDIS)
+    pub fn is_window_mode_forced(&self) -> bool {
+        devcfg::devcfg1() & DEVCFG1_WINDIS_MASK == 0
+    }
+
+    /// Check if window mode is active
+    pub fn is_window_mode(&self) -> bool {
+        self.is_window_mode_forced() || self.wdt.cont.read().winen().bit()
+    }
+
+    /// Enable or disable the window mode
+    ///
+    /// Returns a `ForcedByConfiguration` error when trying to disable the
+    /// window mode if the window mode is always enabled by the configuration
+    /// words.
+    pub fn set_window_mode(&mut self, enable: bool) -> Result<(), Error> {
+        if enable {
+            self.wdt.contset.write(|w| w.winen().bit(true));
+        } else {
+            if self.is_window_mode_forced() {
+                return Err(Error::ForcedByConfiguration);
+            }
+            self.wdt.contclr.write(|w| w.winen().bit(true));
+        }
+        Ok(())
+    }
+
+    /// Get the window size as configured by the configuration words
+    /// (FWDTWINSZ)
+    pub fn window_size(&self) -> WindowSize {
+        match (devcfg::devcfg1() & DEVCFG1_FWDTWINSZ_MASK) >> DEVCFG1_FWDTWINSZ_POSITION {
+            0 => WindowSize::Percent75,
+            1 => WindowSize::Percent50,
+            2 => WindowSize::Percent37_5,
+            _ => WindowSize::Percent25,
+        }
+    }
+
+    /// Get the nominal timeout period in milliseconds as configured by the
+    /// configuration words (WDTPS)
+    ///
+    /// The actual period depends on the accuracy of the LPRC.
+    pub fn timeout_ms(&self) -> u32 {
+        let ps = (devcfg::devcfg1() & DEVCFG1_WDTPS_MASK) >> DEVCFG1_WDTPS_POSITION;
+        // values greater than 20 select the maximum postscaler value
+        1 << ps.min(20)
+    }
+
+    /// Check if the last reset was caused by a watchdog timer timeout
+    ///
+    /// The respective reset cause flag can be cleared with
+    /// `reset::clear_reset_causes()`.
+    pub fn caused_reset(&self) -> bool {
+        reset::reset_causes().contains(ResetCause::Watchdog)
+    }
+}
+
+impl embedded_hal_0_2::watchdog::Watchdog for Watchdog {
+    fn feed(&mut self) {
+        Watchdog::feed(self);
+    }
+}
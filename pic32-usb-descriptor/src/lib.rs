@@ -0,0 +1,405 @@
+//! USB standard requests and descriptors used by the host driver
+//!
+//! This crate does not depend on any hardware.
+
+#![no_std]
+
+/// Descriptor types
+pub const DESCRIPTOR_DEVICE: u8 = 1;
+pub const DESCRIPTOR_CONFIGURATION: u8 = 2;
+pub const DESCRIPTOR_STRING: u8 = 3;
+pub const DESCRIPTOR_INTERFACE: u8 = 4;
+pub const DESCRIPTOR_ENDPOINT: u8 = 5;
+
+/// Standard request codes
+pub const REQUEST_CLEAR_FEATURE: u8 = 1;
+pub const REQUEST_SET_ADDRESS: u8 = 5;
+pub const REQUEST_GET_DESCRIPTOR: u8 = 6;
+pub const REQUEST_SET_CONFIGURATION: u8 = 9;
+
+/// Feature selector ENDPOINT_HALT
+pub const FEATURE_ENDPOINT_HALT: u16 = 0;
+
+/// Setup packet of a control transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SetupPacket {
+    /// Characteristics of the request (bmRequestType)
+    pub request_type: u8,
+    /// Request code (bRequest)
+    pub request: u8,
+    /// Request specific value (wValue)
+    pub value: u16,
+    /// Request specific index (wIndex)
+    pub index: u16,
+    /// Number of bytes of the data stage (wLength)
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// bmRequestType of a device-to-host standard request to the device
+    pub const DEVICE_TO_HOST: u8 = 0x80;
+
+    /// bmRequestType of a host-to-device standard request to the device
+    pub const HOST_TO_DEVICE: u8 = 0x00;
+
+    /// bmRequestType of a host-to-device standard request to an endpoint
+    pub const HOST_TO_ENDPOINT: u8 = 0x02;
+
+    /// GET_DESCRIPTOR request
+    pub const fn get_descriptor(descriptor_type: u8, index: u8, length: u16) -> Self {
+        SetupPacket {
+            request_type: Self::DEVICE_TO_HOST,
+            request: REQUEST_GET_DESCRIPTOR,
+            value: (descriptor_type as u16) << 8 | index as u16,
+            index: 0,
+            length,
+        }
+    }
+
+    /// SET_ADDRESS request
+    pub const fn set_address(address: u8) -> Self {
+        SetupPacket {
+            request_type: Self::HOST_TO_DEVICE,
+            request: REQUEST_SET_ADDRESS,
+            value: address as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// SET_CONFIGURATION request
+    pub const fn set_configuration(value: u8) -> Self {
+        SetupPacket {
+            request_type: Self::HOST_TO_DEVICE,
+            request: REQUEST_SET_CONFIGURATION,
+            value: value as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// CLEAR_FEATURE(ENDPOINT_HALT) request for endpoint address `ep_addr`
+    pub const fn clear_halt(ep_addr: u8) -> Self {
+        SetupPacket {
+            request_type: Self::HOST_TO_ENDPOINT,
+            request: REQUEST_CLEAR_FEATURE,
+            value: FEATURE_ENDPOINT_HALT,
+            index: ep_addr as u16,
+            length: 0,
+        }
+    }
+
+    /// Check if the data stage is device-to-host
+    pub const fn is_in(&self) -> bool {
+        self.request_type & 0x80 != 0
+    }
+
+    /// Serialize to the 8 bytes transferred in the setup stage
+    pub const fn to_bytes(&self) -> [u8; 8] {
+        let value = self.value.to_le_bytes();
+        let index = self.index.to_le_bytes();
+        let length = self.length.to_le_bytes();
+        [
+            self.request_type,
+            self.request,
+            value[0],
+            value[1],
+            index[0],
+            index[1],
+            length[0],
+            length[1],
+        ]
+    }
+}
+
+fn le16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+/// Device descriptor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceDescriptor {
+    /// USB specification release number in BCD (bcdUSB)
+    pub usb_release: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    /// Maximum packet size of endpoint 0
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Device release number in BCD (bcdDevice)
+    pub device_release: u16,
+    /// Indices of the string descriptors
+    pub manufacturer_index: u8,
+    pub product_index: u8,
+    pub serial_number_index: u8,
+    pub num_configurations: u8,
+}
+
+impl DeviceDescriptor {
+    /// Length of a device descriptor
+    pub const LENGTH: usize = 18;
+
+    /// Parse a device descriptor
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::LENGTH
+            || bytes[0] as usize != Self::LENGTH
+            || bytes[1] != DESCRIPTOR_DEVICE
+        {
+            return None;
+        }
+        Some(DeviceDescriptor {
+            usb_release: le16(bytes, 2),
+            class: bytes[4],
+            subclass: bytes[5],
+            protocol: bytes[6],
+            max_packet_size0: bytes[7],
+            vendor_id: le16(bytes, 8),
+            product_id: le16(bytes, 10),
+            device_release: le16(bytes, 12),
+            manufacturer_index: bytes[14],
+            product_index: bytes[15],
+            serial_number_index: bytes[16],
+            num_configurations: bytes[17],
+        })
+    }
+}
+
+/// Header of a configuration descriptor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConfigurationDescriptor {
+    /// Length of the configuration descriptor including all interface,
+    /// endpoint and class specific descriptors
+    pub total_length: u16,
+    pub num_interfaces: u8,
+    /// Value to be used for `SET_CONFIGURATION`
+    pub configuration_value: u8,
+    pub attributes: u8,
+    /// Maximum power consumption in units of 2 mA
+    pub max_power: u8,
+}
+
+impl ConfigurationDescriptor {
+    /// Length of a configuration descriptor header
+    pub const LENGTH: usize = 9;
+
+    /// Parse the header of a configuration descriptor
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::LENGTH
+            || (bytes[0] as usize) < Self::LENGTH
+            || bytes[1] != DESCRIPTOR_CONFIGURATION
+        {
+            return None;
+        }
+        Some(ConfigurationDescriptor {
+            total_length: le16(bytes, 2),
+            num_interfaces: bytes[4],
+            configuration_value: bytes[5],
+            attributes: bytes[7],
+            max_power: bytes[8],
+        })
+    }
+}
+
+/// Interface descriptor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InterfaceDescriptor {
+    pub interface_number: u8,
+    pub alternate_setting: u8,
+    pub num_endpoints: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+}
+
+/// Transfer type of an endpoint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferType {
+    Control,
+    Isochronous,
+    Bulk,
+    Interrupt,
+}
+
+/// Endpoint descriptor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EndpointDescriptor {
+    /// Endpoint address including the direction bit (0x80 for IN)
+    pub address: u8,
+    pub attributes: u8,
+    pub max_packet_size: u16,
+    /// Polling interval in frames
+    pub interval: u8,
+}
+
+impl EndpointDescriptor {
+    /// Endpoint number
+    pub fn number(&self) -> u8 {
+        self.address & 0x0f
+    }
+
+    /// Check if this is an IN endpoint
+    pub fn is_in(&self) -> bool {
+        self.address & 0x80 != 0
+    }
+
+    /// Transfer type
+    pub fn transfer_type(&self) -> TransferType {
+        match self.attributes & 0x03 {
+            0 => TransferType::Control,
+            1 => TransferType::Isochronous,
+            2 => TransferType::Bulk,
+            _ => TransferType::Interrupt,
+        }
+    }
+}
+
+/// Descriptor contained in a configuration descriptor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Descriptor<'a> {
+    Interface(InterfaceDescriptor),
+    Endpoint(EndpointDescriptor),
+    /// Any other descriptor (e.g. class specific descriptors) including the
+    /// length and type bytes
+    Other(&'a [u8]),
+}
+
+/// Iterator over the descriptors following the header of a configuration
+/// descriptor
+pub struct Descriptors<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Descriptors<'a> {
+    /// Create an iterator over the descriptors of a complete configuration
+    /// descriptor
+    ///
+    /// The iteration stops at the first malformed descriptor.
+    pub fn new(configuration: &'a [u8]) -> Self {
+        // bLength may exceed the length of a truncated descriptor
+        let start = match ConfigurationDescriptor::parse(configuration) {
+            Some(_) => (configuration[0] as usize).min(configuration.len()),
+            None => configuration.len(),
+        };
+        Descriptors {
+            bytes: &configuration[start..],
+        }
+    }
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = Descriptor<'a>;
+
+    fn next(&mut self) -> Option<Descriptor<'a>> {
+        let len = *self.bytes.first()? as usize;
+        if len < 2 || len > self.bytes.len() {
+            self.bytes = &[];
+            return None;
+        }
+        let (desc, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(match desc[1] {
+            DESCRIPTOR_INTERFACE if len >= 9 => Descriptor::Interface(InterfaceDescriptor {
+                interface_number: desc[2],
+                alternate_setting: desc[3],
+                num_endpoints: desc[4],
+                class: desc[5],
+                subclass: desc[6],
+                protocol: desc[7],
+            }),
+            DESCRIPTOR_ENDPOINT if len >= 7 => Descriptor::Endpoint(EndpointDescriptor {
+                address: desc[2],
+                attributes: desc[3],
+                max_packet_size: le16(desc, 4) & 0x07ff,
+                interval: desc[6],
+            }),
+            _ => Descriptor::Other(desc),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIGURATION: [u8; 25] = [
+        9, 2, 25, 0, 1, 1, 0, 0x80, 50, // configuration
+        9, 4, 0, 0, 1, 8, 6, 80, 0, // interface
+        7, 5, 0x81, 2, 64, 0, 0, // endpoint
+    ];
+
+    #[test]
+    fn iterate() {
+        let mut descs = Descriptors::new(&CONFIGURATION);
+        assert_eq!(
+            descs.next(),
+            Some(Descriptor::Interface(InterfaceDescriptor {
+                interface_number: 0,
+                alternate_setting: 0,
+                num_endpoints: 1,
+                class: 8,
+                subclass: 6,
+                protocol: 80,
+            }))
+        );
+        let ep = match descs.next() {
+            Some(Descriptor::Endpoint(ep)) => ep,
+            d => panic!("unexpected descriptor {d:?}"),
+        };
+        assert_eq!(ep.number(), 1);
+        assert!(ep.is_in());
+        assert_eq!(ep.transfer_type(), TransferType::Bulk);
+        assert_eq!(ep.max_packet_size, 64);
+        assert_eq!(descs.next(), None);
+    }
+
+    #[test]
+    fn oversized_configuration_length() {
+        // bLength of the configuration descriptor exceeds the buffer
+        let mut conf = CONFIGURATION;
+        conf[0] = 30;
+        assert_eq!(Descriptors::new(&conf).next(), None);
+        conf[0] = 255;
+        assert_eq!(Descriptors::new(&conf[..9]).next(), None);
+    }
+
+    #[test]
+    fn truncated_configuration() {
+        assert_eq!(Descriptors::new(&CONFIGURATION[..5]).next(), None);
+        assert_eq!(Descriptors::new(&CONFIGURATION[..9]).next(), None);
+        // interface descriptor without the endpoint descriptor
+        let mut descs = Descriptors::new(&CONFIGURATION[..18]);
+        assert!(matches!(descs.next(), Some(Descriptor::Interface(_))));
+        assert_eq!(descs.next(), None);
+    }
+
+    #[test]
+    fn malformed_descriptor_length() {
+        // truncated endpoint descriptor
+        let mut descs = Descriptors::new(&CONFIGURATION[..22]);
+        assert!(matches!(descs.next(), Some(Descriptor::Interface(_))));
+        assert_eq!(descs.next(), None);
+        // bLength of the endpoint descriptor exceeds the buffer
+        let mut conf = CONFIGURATION;
+        conf[18] = 8;
+        let mut descs = Descriptors::new(&conf);
+        assert!(matches!(descs.next(), Some(Descriptor::Interface(_))));
+        assert_eq!(descs.next(), None);
+        // bLength less than 2 stops the iteration
+        conf[18] = 0;
+        let mut descs = Descriptors::new(&conf);
+        assert!(matches!(descs.next(), Some(Descriptor::Interface(_))));
+        assert_eq!(descs.next(), None);
+        assert_eq!(descs.next(), None);
+    }
+
+    #[test]
+    fn short_interface_descriptor() {
+        // an interface descriptor shorter than 9 bytes is reported as other
+        let conf = [9, 2, 12, 0, 1, 1, 0, 0x80, 50, 3, 4, 0];
+        let mut descs = Descriptors::new(&conf);
+        assert_eq!(descs.next(), Some(Descriptor::Other(&[3, 4, 0])));
+        assert_eq!(descs.next(), None);
+    }
+}